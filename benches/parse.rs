@@ -0,0 +1,57 @@
+//! Parses large generated documents into a `Value` and reports throughput.
+//!
+//! Run with `cargo bench --bench parse`. This uses a plain timing loop rather
+//! than a benchmarking framework so that it needs no extra dependencies.
+
+extern crate toml;
+
+use std::time::{Duration, Instant};
+
+const ITERATIONS: u32 = 20;
+
+fn flat_table(keys: usize) -> String {
+    let mut s = String::new();
+    for i in 0..keys {
+        s.push_str(&format!("key{} = {}\n", i, i));
+    }
+    s
+}
+
+fn inline_table(keys: usize) -> String {
+    let mut s = String::from("t = { ");
+    for i in 0..keys {
+        if i > 0 {
+            s.push_str(", ");
+        }
+        s.push_str(&format!("key{} = {}", i, i));
+    }
+    s.push_str(" }\n");
+    s
+}
+
+fn bench(name: &str, input: &str) {
+    // Warm up once so allocation of the first run isn't counted.
+    input.parse::<toml::Value>().unwrap();
+
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        input.parse::<toml::Value>().unwrap();
+    }
+    let per_iter = start.elapsed() / ITERATIONS;
+    let secs = as_secs(per_iter);
+    println!(
+        "{:<24} {:>10.2} ms/iter {:>8.1} MB/s",
+        name,
+        secs * 1e3,
+        input.len() as f64 / secs / 1e6
+    );
+}
+
+fn as_secs(d: Duration) -> f64 {
+    d.as_secs() as f64 + d.subsec_nanos() as f64 * 1e-9
+}
+
+fn main() {
+    bench("flat_table_50k", &flat_table(50_000));
+    bench("inline_table_50k", &inline_table(50_000));
+}
@@ -0,0 +1,15 @@
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate toml;
+
+use std::str;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(s) = str::from_utf8(data) {
+        // Errors are fine, panics are not.
+        let _ = s.parse::<toml::Value>();
+        let _ = toml::de::from_str_recover::<toml::Value>(s);
+    }
+    let _ = toml::from_slice::<toml::Value>(data);
+});
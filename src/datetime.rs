@@ -12,7 +12,7 @@ pub(crate) const FIELD: &'static str = "$toml::private::Datetime";
 //TODO(quadrupleslap): This makes the output subsecond a multiple of three digits long.
 //TODO(quadrupleslap): Better error messages that no one will use.
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 /// A parsed TOML datetime value.
 ///
 /// This structure is intended to represent the datetime primitive type that can
@@ -26,6 +26,9 @@ pub(crate) const FIELD: &'static str = "$toml::private::Datetime";
 /// Also note though that while this type implements `Serialize` and
 /// `Deserialize` it's only recommended to use this type with the TOML format,
 /// otherwise encoded in other formats it may look a little odd.
+///
+/// Datetimes of the same kind are ordered chronologically. Offset datetimes
+/// come first, followed by local datetimes, local dates and local times.
 pub enum Datetime {
     /// An date, a time and a timezone.
     OffsetDatetime(chrono::DateTime<chrono::FixedOffset>),
@@ -37,6 +40,68 @@ pub enum Datetime {
     LocalTime(chrono::NaiveTime),
 }
 
+/// Which of the four TOML datetime formats a `Datetime` was written in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DatetimeKind {
+    /// A date, a time and a timezone, such as `1979-05-27T07:32:00Z`.
+    OffsetDatetime,
+    /// A date and a time, such as `1979-05-27T07:32:00`.
+    LocalDatetime,
+    /// A date, such as `1979-05-27`.
+    LocalDate,
+    /// A time, such as `07:32:00`.
+    LocalTime,
+}
+
+impl Datetime {
+    /// Returns which kind of datetime this is.
+    ///
+    /// This is how to reject a bare date where a full timestamp is required:
+    ///
+    /// ```
+    /// use toml::value::{Datetime, DatetimeKind};
+    ///
+    /// let date: Datetime = "1979-05-27".parse().unwrap();
+    /// assert_eq!(date.kind(), DatetimeKind::LocalDate);
+    /// ```
+    pub fn kind(&self) -> DatetimeKind {
+        match self {
+            Datetime::OffsetDatetime(_) => DatetimeKind::OffsetDatetime,
+            Datetime::LocalDatetime(_) => DatetimeKind::LocalDatetime,
+            Datetime::LocalDate(_) => DatetimeKind::LocalDate,
+            Datetime::LocalTime(_) => DatetimeKind::LocalTime,
+        }
+    }
+
+    /// Returns the calendar date, if this datetime has one.
+    pub fn date(&self) -> Option<chrono::NaiveDate> {
+        match self {
+            Datetime::OffsetDatetime(x) => Some(x.naive_local().date()),
+            Datetime::LocalDatetime(x) => Some(x.date()),
+            Datetime::LocalDate(x) => Some(*x),
+            Datetime::LocalTime(_) => None,
+        }
+    }
+
+    /// Returns the time of day, if this datetime has one.
+    pub fn time(&self) -> Option<chrono::NaiveTime> {
+        match self {
+            Datetime::OffsetDatetime(x) => Some(x.naive_local().time()),
+            Datetime::LocalDatetime(x) => Some(x.time()),
+            Datetime::LocalDate(_) => None,
+            Datetime::LocalTime(x) => Some(*x),
+        }
+    }
+
+    /// Returns the UTC offset, if this is an offset datetime.
+    pub fn offset(&self) -> Option<chrono::FixedOffset> {
+        match self {
+            Datetime::OffsetDatetime(x) => Some(*x.offset()),
+            _ => None,
+        }
+    }
+}
+
 impl FromStr for Datetime {
     type Err = DatetimeParseError;
 
@@ -5,9 +5,11 @@
 //! provided at the top of the crate.
 
 use std::borrow::Cow;
+use std::cmp;
 use std::error;
 use std::f64;
 use std::fmt;
+use std::io::{self, Read};
 use std::str;
 use std::vec;
 
@@ -22,21 +24,100 @@ use tokens::{Error as TokenError, Span, Token, Tokenizer};
 /// Deserializes a byte slice into a type.
 ///
 /// This function will attempt to interpret `bytes` as UTF-8 data and then
-/// deserialize `T` from the TOML document provided.
+/// deserialize `T` from the TOML document provided. If `bytes` is not valid
+/// UTF-8 the returned error points at the first invalid byte.
 pub fn from_slice<'de, T>(bytes: &'de [u8]) -> Result<T, Error>
 where
     T: de::Deserialize<'de>,
 {
     match str::from_utf8(bytes) {
         Ok(s) => from_str(s),
-        Err(e) => Err(Error::custom(e.to_string())),
+        Err(e) => {
+            let valid = str::from_utf8(&bytes[..e.valid_up_to()]).unwrap();
+            let d = Deserializer::new(valid);
+            let mut err = d.error(valid.len(), ErrorKind::Custom);
+            err.inner.message = e.to_string();
+            Err(err)
+        }
     }
 }
 
+/// Deserializes the contents of a reader into a type.
+///
+/// The whole of `reader` is read into memory before parsing, so positions in
+/// errors are relative to the start of its contents. Failing to read from
+/// `reader` and invalid UTF-8 are both reported as an `Error`.
+///
+/// # Examples
+///
+/// ```
+/// #[macro_use]
+/// extern crate serde_derive;
+/// extern crate toml;
+///
+/// #[derive(Deserialize)]
+/// struct Config {
+///     title: String,
+/// }
+///
+/// fn main() {
+///     let file = "title = 'TOML Example'".as_bytes();
+///     let config: Config = toml::from_reader(file).unwrap();
+///
+///     assert_eq!(config.title, "TOML Example");
+/// }
+/// ```
+pub fn from_reader<R, T>(mut reader: R) -> Result<T, Error>
+where
+    R: io::Read,
+    T: de::DeserializeOwned,
+{
+    let mut bytes = Vec::new();
+    if let Err(e) = reader.read_to_end(&mut bytes) {
+        return Err(Error::custom(e.to_string()));
+    }
+    from_slice(&bytes)
+}
+
+/// Deserializes the contents of a reader into a type, reading at most
+/// `max_len` bytes.
+///
+/// This behaves like `from_reader`, except that reading stops as soon as the
+/// input is found to be longer than `max_len`, and an error is returned
+/// without parsing any of it. This bounds the memory used for untrusted
+/// input, and complements `Deserializer::set_max_depth`.
+///
+/// # Examples
+///
+/// ```
+/// let input = "title = 'TOML Example'".as_bytes();
+/// let err = toml::from_reader_with_max_len::<_, toml::Value>(input, 8).unwrap_err();
+///
+/// assert_eq!(err.to_string(), "input is longer than the maximum of 8 bytes");
+/// ```
+pub fn from_reader_with_max_len<R, T>(reader: R, max_len: usize) -> Result<T, Error>
+where
+    R: io::Read,
+    T: de::DeserializeOwned,
+{
+    let mut bytes = Vec::new();
+    let limit = (max_len as u64).saturating_add(1);
+    if let Err(e) = reader.take(limit).read_to_end(&mut bytes) {
+        return Err(Error::custom(e.to_string()));
+    }
+    if bytes.len() > max_len {
+        return Err(Error::from_kind(ErrorKind::InputTooLong(max_len)));
+    }
+    from_slice(&bytes)
+}
+
 /// Deserializes a string into a type.
 ///
 /// This function will attempt to interpret `s` as a TOML document and
-/// deserialize `T` from the document.
+/// deserialize `T` from the document. Malformed input of any kind is reported
+/// as an `Error` rather than a panic, so untrusted documents can be parsed
+/// directly; a fuzz target for this lives in the repository's `fuzz`
+/// directory.
 ///
 /// # Examples
 ///
@@ -78,6 +159,77 @@ where
     Ok(ret)
 }
 
+/// Deserializes a string into a type, recovering from syntax errors.
+///
+/// Unlike `from_str`, parsing does not stop at the first malformed line.
+/// Instead the error is recorded, the rest of the line is skipped and parsing
+/// resumes on the next one. Key/value pairs below a malformed table header are
+/// skipped along with the header itself. Whatever was parsed successfully is
+/// then deserialized into `T`.
+///
+/// All errors encountered are returned in the order they occurred, each with
+/// its own position. The value is `None` if `T` could not be deserialized from
+/// the recovered document, in which case the last error describes why.
+///
+/// # Examples
+///
+/// ```
+/// let (value, errors) = toml::de::from_str_recover::<toml::Value>("
+///     a = 1
+///     b = @
+///     c = 3
+///     d = =
+/// ");
+///
+/// assert_eq!(errors.len(), 2);
+/// let value = value.unwrap();
+/// assert_eq!(value["a"].as_integer(), Some(1));
+/// assert_eq!(value["c"].as_integer(), Some(3));
+/// ```
+pub fn from_str_recover<'de, T>(s: &'de str) -> (Option<T>, Vec<Error>)
+where
+    T: de::Deserialize<'de>,
+{
+    let mut d = Deserializer::new(s);
+    d.errors = Some(Vec::new());
+    let ret = T::deserialize(&mut d).and_then(|ret| d.end().map(|()| ret));
+    let mut errors = d.errors.take().unwrap_or_default();
+    // Duplicate keys are only found once their table is complete, so put
+    // everything back in source order.
+    errors.sort_by_key(|e| e.line_col());
+    match ret {
+        Ok(ret) => (Some(ret), errors),
+        Err(e) => {
+            errors.push(e);
+            (None, errors)
+        }
+    }
+}
+
+/// Checks whether a string is a valid TOML document without keeping any of
+/// its contents.
+///
+/// Every error `from_str_recover` would report is returned, in the order
+/// they occurred. Nothing is deserialized, so unlike `from_str` this cannot
+/// fail because of a missing field or a value of the wrong type.
+///
+/// # Examples
+///
+/// ```
+/// assert!(toml::validate("a = 1\n[b]\nc = 'x'").is_ok());
+///
+/// let errors = toml::validate("a = @\n[b]\nc = 'x\n[b]").unwrap_err();
+/// assert_eq!(errors.len(), 3);
+/// ```
+pub fn validate(s: &str) -> Result<(), Vec<Error>> {
+    let (_, errors) = from_str_recover::<de::IgnoredAny>(s);
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
 /// Errors that can occur when deserializing a type.
 #[derive(Debug, Clone)]
 pub struct Error {
@@ -91,11 +243,75 @@ struct ErrorInner {
     col: usize,
     message: String,
     key: Vec<String>,
+    path: Vec<String>,
+}
+
+/// The broad category of an `Error`, as returned by `Error::classify`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Category {
+    /// The input was not a valid TOML document, for example because of a
+    /// stray character, a duplicate key or invalid UTF-8.
+    Syntax,
+
+    /// The input was valid TOML, but did not match the type being
+    /// deserialized, for example because of a missing field or a value of the
+    /// wrong type.
+    Data,
+}
+
+/// What went wrong to cause an `Error`, as returned by `Error::code`.
+///
+/// Unlike the message of an error, codes are meant to be matched on, for
+/// example to react to certain mistakes in a document or to translate
+/// messages. More codes may be added in the future, so a `match` on a code
+/// should always have a wildcard arm.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Code {
+    /// A character or token appeared where it isn't allowed, or the document
+    /// ended early.
+    UnexpectedToken,
+
+    /// A string was unterminated, or held an invalid character or escape.
+    InvalidString,
+
+    /// A key was empty, multiline, or held a newline.
+    InvalidKey,
+
+    /// A number was malformed, or an integer didn't fit in an `i64`.
+    InvalidNumber,
+
+    /// A date, time or datetime was cut short or had stray characters.
+    ///
+    /// Datetimes which are well-formed but name an impossible date or time,
+    /// such as February 30th, are only rejected when they are deserialized,
+    /// and have the code `Other`.
+    InvalidDatetime,
+
+    /// An array held values of different types.
+    MixedArray,
+
+    /// A key was defined twice, or a dotted key tried to extend a value which
+    /// is not a table.
+    DuplicateKey,
+
+    /// A table header defined a table which already existed, or turned one
+    /// into an array of tables.
+    InvalidTableHeader,
+
+    /// The document was nested too deeply or was too long.
+    LimitExceeded,
+
+    /// The error was raised while deserializing the parsed document, or by
+    /// the type being deserialized. `Error::classify` tells these apart.
+    Other,
+
+    #[doc(hidden)]
+    __Nonexhaustive,
 }
 
 /// Errors that can occur when deserializing a type.
 #[derive(Debug, Clone)]
-enum ErrorKind {
+pub(crate) enum ErrorKind {
     /// EOF was reached when looking for a value
     UnexpectedEof,
 
@@ -130,6 +346,9 @@ enum ErrorKind {
     /// A number failed to parse
     NumberInvalid,
 
+    /// An integer was too large or too small to fit in an `i64`.
+    IntegerOverflow(String),
+
     /// A date or datetime was invalid
     DateInvalid,
 
@@ -143,10 +362,28 @@ enum ErrorKind {
 
     /// An array was decoded but the types inside of it were mixed, which is
     /// disallowed by TOML.
-    MixedArrayType,
+    MixedArrayType {
+        /// Type of the elements before the offending one
+        expected: &'static str,
+        /// Type of the offending element
+        found: &'static str,
+    },
 
     /// A duplicate table definition was found.
-    DuplicateTable(String),
+    DuplicateTable {
+        /// The dotted name of the table
+        name: String,
+        /// The line the table was first defined on
+        first: usize,
+    },
+
+    /// A key was defined more than once in the same table.
+    DuplicateKey {
+        /// The key that was redefined
+        key: String,
+        /// The line the key was first defined on
+        first: usize,
+    },
 
     /// A previously defined table was redefined as an array.
     RedefineAsArray,
@@ -174,11 +411,42 @@ enum ErrorKind {
         found: String,
     },
 
+    /// An array was deserialized into a tuple or fixed-size array with a
+    /// different number of elements.
+    ArrayLength {
+        /// Number of elements the tuple or array holds.
+        expected: usize,
+        /// Number of elements in the array.
+        found: usize,
+    },
+
+    /// A value was deserialized into a type which can't hold it, such as a
+    /// string into an integer field.
+    ///
+    /// The value itself, as rendered by serde, is kept in the error message.
+    TypeMismatch {
+        /// What the type being deserialized expected.
+        expected: String,
+        /// Type of the value that was found.
+        found: &'static str,
+    },
+
     /// An empty table was expected but entries were found
     ExpectedEmptyTable,
 
     /// Dotted key attempted to extend something that is not a table.
-    DottedKeyInvalidType,
+    DottedKeyInvalidType {
+        /// The key of the value which is not a table
+        key: String,
+        /// The line the value was defined on
+        first: usize,
+    },
+
+    /// Tables and arrays were nested deeper than allowed.
+    MaxDepthExceeded(usize),
+
+    /// The input was longer than the given number of bytes.
+    InputTooLong(usize),
 
     /// An unexpected key was encountered.
     ///
@@ -190,14 +458,62 @@ enum ErrorKind {
         available: &'static [&'static str],
     },
 
+    /// Two keys of a table matched the same struct field when compared
+    /// without regard to case.
+    DuplicateKeyIgnoringCase {
+        /// The field both keys matched.
+        field: &'static str,
+        /// The key that was seen first.
+        first: String,
+        /// The key that was seen second.
+        second: String,
+    },
+
     #[doc(hidden)]
     __Nonexhaustive,
 }
 
+/// How deeply tables and arrays may be nested unless configured otherwise.
+pub(crate) const DEFAULT_MAX_DEPTH: usize = 128;
+
+/// Options controlling how table keys are matched against struct fields and
+/// how loosely values are read, passed down to the deserializer of every
+/// nested value.
+#[derive(Clone, Copy, Default)]
+struct ValueOptions {
+    deny_unknown: bool,
+    ignore_case: bool,
+    coerce_strings: bool,
+}
+
+impl ValueOptions {
+    fn matches_fields_exactly(&self) -> bool {
+        !self.deny_unknown && !self.ignore_case
+    }
+
+    /// Finds the field named by `key`, preferring an exact match over one
+    /// which only differs in case.
+    fn find(&self, fields: &'static [&'static str], key: &str) -> Option<&'static str> {
+        if let Some(field) = fields.iter().find(|f| **f == key) {
+            return Some(*field);
+        }
+        if !self.ignore_case {
+            return None;
+        }
+        let key = key.to_lowercase();
+        fields.iter().find(|f| f.to_lowercase() == key).map(|f| *f)
+    }
+}
+
 /// Deserialization implementation for TOML.
 pub struct Deserializer<'a> {
     require_newline_after_table: bool,
     allow_duplciate_after_longer_table: bool,
+    options: ValueOptions,
+    max_depth: usize,
+    depth: usize,
+    normalize_keys: Option<fn(&str) -> String>,
+    errors: Option<Vec<Error>>,
     input: &'a str,
     tokens: Tokenizer<'a>,
 }
@@ -214,6 +530,8 @@ impl<'de, 'b> de::Deserializer<'de> for &'b mut Deserializer<'de> {
         visitor.visit_map(MapVisitor {
             values: Vec::new().into_iter(),
             next_value: None,
+            fields: None,
+            matched: Vec::new(),
             depth: 0,
             cur: 0,
             cur_parent: 0,
@@ -224,6 +542,37 @@ impl<'de, 'b> de::Deserializer<'de> for &'b mut Deserializer<'de> {
         })
     }
 
+    fn deserialize_struct<V>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        let mut tables = self.tables()?;
+
+        de::Deserializer::deserialize_struct(
+            MapVisitor {
+                values: Vec::new().into_iter(),
+                next_value: None,
+                fields: None,
+                matched: Vec::new(),
+                depth: 0,
+                cur: 0,
+                cur_parent: 0,
+                max: tables.len(),
+                tables: &mut tables,
+                array: false,
+                de: self,
+            },
+            name,
+            fields,
+            visitor,
+        )
+    }
+
     // Called when the type to deserialize is an enum, as opposed to a field in the type.
     fn deserialize_enum<V>(
         self,
@@ -251,12 +600,14 @@ impl<'de, 'b> de::Deserializer<'de> for &'b mut Deserializer<'de> {
                     visitor.visit_enum(InlineTableDeserializer {
                         values: values.into_iter(),
                         next_value: None,
+                        options: self.options,
                     })
                 }
             }
             E::DottedTable(_) => visitor.visit_enum(DottedTableDeserializer {
                 name: name.expect("Expected table header to be passed."),
                 value: value,
+                options: self.options,
             }),
             e @ _ => Err(Error::from_kind(ErrorKind::Wanted {
                 expected: "string or table",
@@ -265,10 +616,18 @@ impl<'de, 'b> de::Deserializer<'de> for &'b mut Deserializer<'de> {
         }
     }
 
+    fn deserialize_unit_struct<V>(self, name: &'static str, visitor: V) -> Result<V::Value, Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        let value: ::value::Value = de::Deserialize::deserialize(self)?;
+        de::Deserializer::deserialize_unit_struct(value, name, visitor)
+    }
+
     forward_to_deserialize_any! {
         bool u8 u16 u32 u64 i8 i16 i32 i64 f32 f64 char str string seq
-        bytes byte_buf map struct unit newtype_struct
-        ignored_any unit_struct tuple_struct tuple option identifier
+        bytes byte_buf map unit newtype_struct
+        ignored_any tuple_struct tuple option identifier
     }
 }
 
@@ -283,6 +642,8 @@ struct Table<'a> {
 pub struct MapVisitor<'de: 'b, 'b> {
     values: vec::IntoIter<(Cow<'de, str>, Value<'de>)>,
     next_value: Option<(Cow<'de, str>, Value<'de>)>,
+    fields: Option<&'static [&'static str]>,
+    matched: Vec<(&'static str, Cow<'de, str>)>,
     depth: usize,
     cur: usize,
     cur_parent: usize,
@@ -306,7 +667,10 @@ impl<'de, 'b> de::MapAccess<'de> for MapVisitor<'de, 'b> {
         loop {
             assert!(self.next_value.is_none());
             if let Some((key, value)) = self.values.next() {
-                let ret = seed.deserialize(StrDeserializer::new(key.clone()))?;
+                let field =
+                    self.de
+                        .field_key(self.fields, &mut self.matched, key.clone(), value.start)?;
+                let ret = seed.deserialize(StrDeserializer::new(field))?;
                 self.next_value = Some((key, value));
                 return Ok(Some(ret));
             }
@@ -340,7 +704,12 @@ impl<'de, 'b> de::MapAccess<'de> for MapVisitor<'de, 'b> {
                 if self.tables[self.cur_parent].header == self.tables[pos].header {
                     let at = self.tables[pos].at;
                     let name = self.tables[pos].header.join(".");
-                    return Err(self.de.error(at, ErrorKind::DuplicateTable(name)));
+                    let first = self.de.to_linecol(self.tables[self.cur_parent].at).0;
+                    let kind = ErrorKind::DuplicateTable {
+                        name: name,
+                        first: first,
+                    };
+                    return Err(self.de.error(at, kind));
                 }
 
                 // If we're here we know we should share the same prefix, and if
@@ -362,8 +731,11 @@ impl<'de, 'b> de::MapAccess<'de> for MapVisitor<'de, 'b> {
             // just next the next portion of its header and then continue
             // decoding.
             if self.depth != table.header.len() {
-                let key = &table.header[self.depth];
-                let key = seed.deserialize(StrDeserializer::new(key.clone()))?;
+                let key = table.header[self.depth].clone();
+                let key = self
+                    .de
+                    .field_key(self.fields, &mut self.matched, key, table.at)?;
+                let key = seed.deserialize(StrDeserializer::new(key))?;
                 return Ok(Some(key));
             }
 
@@ -389,7 +761,8 @@ impl<'de, 'b> de::MapAccess<'de> for MapVisitor<'de, 'b> {
         V: de::DeserializeSeed<'de>,
     {
         if let Some((k, v)) = self.next_value.take() {
-            match seed.deserialize(ValueDeserializer::new(v)) {
+            let de = ValueDeserializer::new(v).with_options(self.de.options);
+            match seed.deserialize(de) {
                 Ok(v) => return Ok(v),
                 Err(mut e) => {
                     e.add_key_context(&k);
@@ -404,6 +777,8 @@ impl<'de, 'b> de::MapAccess<'de> for MapVisitor<'de, 'b> {
         let res = seed.deserialize(MapVisitor {
             values: Vec::new().into_iter(),
             next_value: None,
+            fields: None,
+            matched: Vec::new(),
             depth: self.depth + if array { 0 } else { 1 },
             cur_parent: self.cur - 1,
             cur: 0,
@@ -448,6 +823,8 @@ impl<'de, 'b> de::SeqAccess<'de> for MapVisitor<'de, 'b> {
                 .expect("Unable to read table values")
                 .into_iter(),
             next_value: None,
+            fields: None,
+            matched: Vec::new(),
             depth: self.depth + 1,
             cur_parent: self.cur_parent,
             max: next,
@@ -455,7 +832,29 @@ impl<'de, 'b> de::SeqAccess<'de> for MapVisitor<'de, 'b> {
             array: false,
             tables: &mut self.tables,
             de: &mut self.de,
-        })?;
+        });
+        let ret = match ret {
+            Ok(ret) => ret,
+            Err(mut e) => {
+                // A table header which clashes with this element is a problem
+                // with the header rather than with the element's values, so it
+                // doesn't get the index of the element.
+                match e.inner.kind {
+                    ErrorKind::DuplicateTable { .. } | ErrorKind::RedefineAsArray => {}
+                    _ => {
+                        // Only work out which element this was once something
+                        // has gone wrong with it.
+                        let header = &self.tables[self.cur_parent].header;
+                        let index = self.tables[..self.cur_parent]
+                            .iter()
+                            .filter(|table| table.array && table.header == *header)
+                            .count();
+                        e.add_key_context(&index.to_string());
+                    }
+                }
+                return Err(e);
+            }
+        };
         self.cur_parent = next;
         Ok(Some(ret))
     }
@@ -495,23 +894,83 @@ impl<'de, 'b> de::Deserializer<'de> for MapVisitor<'de, 'b> {
         visitor.visit_newtype_struct(self)
     }
 
+    fn deserialize_struct<V>(
+        mut self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        if !self.de.options.matches_fields_exactly() && !self.array {
+            self.fields = Some(fields);
+        }
+        self.deserialize_any(visitor)
+    }
+
+    // Enums spelled out with table headers, such as `[e.Variant]`, are
+    // collected into a `Value` first which reads the single key of the table
+    // as the variant name.
+    fn deserialize_enum<V>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        let value: ::value::Value = de::Deserialize::deserialize(self)?;
+        de::Deserializer::deserialize_enum(value, name, variants, visitor)
+    }
+
+    fn deserialize_unit_struct<V>(self, name: &'static str, visitor: V) -> Result<V::Value, Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        let value: ::value::Value = de::Deserialize::deserialize(self)?;
+        de::Deserializer::deserialize_unit_struct(value, name, visitor)
+    }
+
     forward_to_deserialize_any! {
         bool u8 u16 u32 u64 i8 i16 i32 i64 f32 f64 char str string seq
-        bytes byte_buf map struct unit identifier
-        ignored_any unit_struct tuple_struct tuple enum
+        bytes byte_buf map unit identifier
+        ignored_any tuple_struct tuple
     }
 }
 
-struct StrDeserializer<'a> {
+pub(crate) struct StrDeserializer<'a> {
     key: Cow<'a, str>,
 }
 
 impl<'a> StrDeserializer<'a> {
-    fn new(key: Cow<'a, str>) -> StrDeserializer<'a> {
+    pub(crate) fn new(key: Cow<'a, str>) -> StrDeserializer<'a> {
         StrDeserializer { key: key }
     }
 }
 
+// Keys are always strings in TOML, but maps with integer or boolean keys are
+// still useful, so parse the key when one of those is asked for.
+macro_rules! deserialize_parsed_key {
+    ($($method:ident => $visit:ident,)*) => {
+        $(
+            fn $method<V>(self, visitor: V) -> Result<V::Value, Error>
+            where
+                V: de::Visitor<'de>,
+            {
+                match self.key.parse() {
+                    Ok(v) => visitor.$visit(v),
+                    Err(_) => Err(de::Error::invalid_value(
+                        de::Unexpected::Str(&self.key),
+                        &visitor,
+                    )),
+                }
+            }
+        )*
+    };
+}
+
 impl<'de> de::Deserializer<'de> for StrDeserializer<'de> {
     type Error = Error;
 
@@ -525,8 +984,20 @@ impl<'de> de::Deserializer<'de> for StrDeserializer<'de> {
         }
     }
 
+    deserialize_parsed_key! {
+        deserialize_bool => visit_bool,
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+    }
+
     forward_to_deserialize_any! {
-        bool u8 u16 u32 u64 i8 i16 i32 i64 f32 f64 char str string seq
+        f32 f64 char str string seq
         bytes byte_buf map struct option unit newtype_struct
         ignored_any unit_struct tuple_struct tuple enum identifier
     }
@@ -535,6 +1006,7 @@ impl<'de> de::Deserializer<'de> for StrDeserializer<'de> {
 struct ValueDeserializer<'a> {
     value: Value<'a>,
     validate_struct_keys: bool,
+    options: ValueOptions,
 }
 
 impl<'a> ValueDeserializer<'a> {
@@ -542,6 +1014,7 @@ impl<'a> ValueDeserializer<'a> {
         ValueDeserializer {
             value: value,
             validate_struct_keys: false,
+            options: ValueOptions::default(),
         }
     }
 
@@ -549,6 +1022,34 @@ impl<'a> ValueDeserializer<'a> {
         self.validate_struct_keys = true;
         self
     }
+
+    // Unlike `with_struct_key_validation` this is passed on to every nested
+    // value as well.
+    fn with_options(mut self, options: ValueOptions) -> Self {
+        self.options = options;
+        self
+    }
+}
+
+// With `Deserializer::set_coerce_strings` strings are parsed into the
+// booleans and numbers asked for, much like keys are.
+macro_rules! deserialize_coerced {
+    ($($method:ident => $visit:ident,)*) => {
+        $(
+            fn $method<V>(self, visitor: V) -> Result<V::Value, Error>
+            where
+                V: de::Visitor<'de>,
+            {
+                match self.value.e {
+                    E::String(ref s) if self.options.coerce_strings => match s.parse() {
+                        Ok(v) => visitor.$visit(v),
+                        Err(_) => Err(de::Error::invalid_value(de::Unexpected::Str(s), &visitor)),
+                    },
+                    _ => self.deserialize_any(visitor),
+                }
+            }
+        )*
+    };
 }
 
 impl<'de> de::Deserializer<'de> for ValueDeserializer<'de> {
@@ -569,22 +1070,31 @@ impl<'de> de::Deserializer<'de> for ValueDeserializer<'de> {
                 visited: false,
             }),
             E::Array(values) => {
-                let mut s = de::value::SeqDeserializer::new(values.into_iter());
+                let len = values.len();
+                let mut s = ArrayDeserializer {
+                    values: values.into_iter(),
+                    index: 0,
+                    options: self.options,
+                };
                 let ret = visitor.visit_seq(&mut s)?;
-                s.end()?;
-                Ok(ret)
+                if s.values.len() == 0 {
+                    Ok(ret)
+                } else {
+                    Err(de::Error::invalid_length(len, &"fewer elements in array"))
+                }
             }
             E::InlineTable(values) | E::DottedTable(values) => {
                 visitor.visit_map(InlineTableDeserializer {
                     values: values.into_iter(),
                     next_value: None,
+                    options: self.options,
                 })
             }
         }
     }
 
     fn deserialize_struct<V>(
-        self,
+        mut self,
         name: &'static str,
         fields: &'static [&'static str],
         visitor: V,
@@ -601,7 +1111,42 @@ impl<'de> de::Deserializer<'de> for ValueDeserializer<'de> {
             }
         }
 
-        if self.validate_struct_keys {
+        if name == spanned::NAME && fields == &[spanned::START, spanned::END, spanned::VALUE] {
+            let start = self.value.start;
+            let end = self.value.end;
+
+            return visitor.visit_map(SpannedDeserializer {
+                start: Some(start),
+                value: Some(self),
+                end: Some(end),
+            });
+        }
+
+        if self.options.ignore_case {
+            match self.value.e {
+                E::InlineTable(ref mut values) | E::DottedTable(ref mut values) => {
+                    let mut matched: Vec<(&'static str, Cow<'de, str>)> = Vec::new();
+                    for &mut (ref mut key, _) in values.iter_mut() {
+                        let field = match self.options.find(fields, key) {
+                            Some(field) => field,
+                            None => continue,
+                        };
+                        if let Some(&(_, ref first)) = matched.iter().find(|m| m.0 == field) {
+                            return Err(Error::from_kind(ErrorKind::DuplicateKeyIgnoringCase {
+                                field: field,
+                                first: first.to_string(),
+                                second: key.to_string(),
+                            }));
+                        }
+                        matched.push((field, key.clone()));
+                        *key = Cow::Borrowed(field);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if self.validate_struct_keys || self.options.deny_unknown {
             match &self.value.e {
                 &E::InlineTable(ref values) | &E::DottedTable(ref values) => {
                     let extra_fields = values
@@ -630,17 +1175,6 @@ impl<'de> de::Deserializer<'de> for ValueDeserializer<'de> {
             }
         }
 
-        if name == spanned::NAME && fields == &[spanned::START, spanned::END, spanned::VALUE] {
-            let start = self.value.start;
-            let end = self.value.end;
-
-            return visitor.visit_map(SpannedDeserializer {
-                start: Some(start),
-                value: Some(self.value),
-                end: Some(end),
-            });
-        }
-
         self.deserialize_any(visitor)
     }
 
@@ -678,6 +1212,7 @@ impl<'de> de::Deserializer<'de> for ValueDeserializer<'de> {
                     visitor.visit_enum(InlineTableDeserializer {
                         values: values.into_iter(),
                         next_value: None,
+                        options: self.options,
                     })
                 }
             }
@@ -699,10 +1234,133 @@ impl<'de> de::Deserializer<'de> for ValueDeserializer<'de> {
         visitor.visit_newtype_struct(self)
     }
 
+    // Types which are written as strings in other formats, such as chrono's
+    // date and time types, ask for a string. Hand them datetimes in the same
+    // text form that `Datetime` displays.
+    fn deserialize_str<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        if let E::Datetime(s) = self.value.e {
+            let datetime = s.parse::<datetime::Datetime>().map_err(de::Error::custom)?;
+            let result = visitor.visit_string(datetime.to_string());
+            return result.map_err(|mut e: Error| {
+                if let ErrorKind::Custom = e.inner.kind {
+                    e.inner.message =
+                        format!("invalid datetime `{}`: {}", datetime, e.inner.message);
+                }
+                e
+            });
+        }
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_str(visitor)
+    }
+
+    // TOML has no unit type, so unit structs are read from empty tables.
+    fn deserialize_unit_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value, Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        let empty = match self.value.e {
+            E::InlineTable(ref values) | E::DottedTable(ref values) => Some(values.is_empty()),
+            _ => None,
+        };
+        match empty {
+            Some(true) => visitor.visit_unit(),
+            Some(false) => Err(Error::from_kind(ErrorKind::ExpectedEmptyTable)),
+            None => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value, Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        if let E::Array(ref values) = self.value.e {
+            if values.len() != len {
+                return Err(Error::from_kind(ErrorKind::ArrayLength {
+                    expected: len,
+                    found: values.len(),
+                }));
+            }
+        }
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_tuple_struct<V>(
+        self,
+        _name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_tuple(len, visitor)
+    }
+
+    deserialize_coerced! {
+        deserialize_bool => visit_bool,
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+    }
+
+    // The value was already parsed in full, so skip walking through it when
+    // it's only going to be thrown away.
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+
     forward_to_deserialize_any! {
-        bool u8 u16 u32 u64 i8 i16 i32 i64 f32 f64 char str string seq
-        bytes byte_buf map unit identifier
-        ignored_any unit_struct tuple_struct tuple
+        char seq bytes byte_buf map unit identifier
+    }
+}
+
+struct ArrayDeserializer<'a> {
+    values: vec::IntoIter<Value<'a>>,
+    index: usize,
+    options: ValueOptions,
+}
+
+impl<'de> de::SeqAccess<'de> for ArrayDeserializer<'de> {
+    type Error = Error;
+
+    fn next_element_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Error>
+    where
+        K: de::DeserializeSeed<'de>,
+    {
+        let value = match self.values.next() {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+        let index = self.index;
+        self.index += 1;
+        let de = ValueDeserializer::new(value).with_options(self.options);
+        seed.deserialize(de).map(Some).map_err(|mut e| {
+            e.add_key_context(&index.to_string());
+            e
+        })
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.values.len())
     }
 }
 
@@ -717,7 +1375,7 @@ impl<'de> de::IntoDeserializer<'de, Error> for Value<'de> {
 struct SpannedDeserializer<'a> {
     start: Option<usize>,
     end: Option<usize>,
-    value: Option<Value<'a>>,
+    value: Option<ValueDeserializer<'a>>,
 }
 
 impl<'de> de::MapAccess<'de> for SpannedDeserializer<'de> {
@@ -750,7 +1408,7 @@ impl<'de> de::MapAccess<'de> for SpannedDeserializer<'de> {
         } else if let Some(end) = self.end.take() {
             seed.deserialize(end.into_deserializer())
         } else if let Some(value) = self.value.take() {
-            seed.deserialize(value.into_deserializer())
+            seed.deserialize(value)
         } else {
             panic!("next_value_seed called before next_key_seed")
         }
@@ -806,6 +1464,7 @@ impl<'de> de::Deserializer<'de> for DatetimeFieldDeserializer {
 struct DottedTableDeserializer<'a> {
     name: Cow<'a, str>,
     value: Value<'a>,
+    options: ValueOptions,
 }
 
 impl<'de> de::EnumAccess<'de> for DottedTableDeserializer<'de> {
@@ -816,15 +1475,19 @@ impl<'de> de::EnumAccess<'de> for DottedTableDeserializer<'de> {
     where
         V: de::DeserializeSeed<'de>,
     {
-        let (name, value) = (self.name, self.value);
-        seed.deserialize(StrDeserializer::new(name))
-            .map(|val| (val, TableEnumDeserializer { value: value }))
+        let variant = TableEnumDeserializer {
+            value: self.value,
+            options: self.options,
+        };
+        seed.deserialize(StrDeserializer::new(self.name))
+            .map(|val| (val, variant))
     }
 }
 
 struct InlineTableDeserializer<'a> {
     values: vec::IntoIter<(Cow<'a, str>, Value<'a>)>,
-    next_value: Option<Value<'a>>,
+    next_value: Option<(Cow<'a, str>, Value<'a>)>,
+    options: ValueOptions,
 }
 
 impl<'de> de::MapAccess<'de> for InlineTableDeserializer<'de> {
@@ -838,7 +1501,7 @@ impl<'de> de::MapAccess<'de> for InlineTableDeserializer<'de> {
             Some(pair) => pair,
             None => return Ok(None),
         };
-        self.next_value = Some(value);
+        self.next_value = Some((key.clone(), value));
         seed.deserialize(StrDeserializer::new(key)).map(Some)
     }
 
@@ -846,8 +1509,12 @@ impl<'de> de::MapAccess<'de> for InlineTableDeserializer<'de> {
     where
         V: de::DeserializeSeed<'de>,
     {
-        let value = self.next_value.take().expect("Unable to read table values");
-        seed.deserialize(ValueDeserializer::new(value))
+        let (key, value) = self.next_value.take().expect("Unable to read table values");
+        let de = ValueDeserializer::new(value).with_options(self.options);
+        seed.deserialize(de).map_err(|mut e| {
+            e.add_key_context(&key);
+            e
+        })
     }
 }
 
@@ -869,14 +1536,19 @@ impl<'de> de::EnumAccess<'de> for InlineTableDeserializer<'de> {
             }
         };
 
+        let variant = TableEnumDeserializer {
+            value: value,
+            options: self.options,
+        };
         seed.deserialize(StrDeserializer::new(key))
-            .map(|val| (val, TableEnumDeserializer { value: value }))
+            .map(|val| (val, variant))
     }
 }
 
 /// Deserializes table values into enum variants.
 struct TableEnumDeserializer<'a> {
     value: Value<'a>,
+    options: ValueOptions,
 }
 
 impl<'de> de::VariantAccess<'de> for TableEnumDeserializer<'de> {
@@ -902,7 +1574,8 @@ impl<'de> de::VariantAccess<'de> for TableEnumDeserializer<'de> {
     where
         T: de::DeserializeSeed<'de>,
     {
-        seed.deserialize(ValueDeserializer::new(self.value))
+        let de = ValueDeserializer::new(self.value).with_options(self.options);
+        seed.deserialize(de)
     }
 
     fn tuple_variant<V>(self, len: usize, visitor: V) -> Result<V::Value, Self::Error>
@@ -939,7 +1612,8 @@ impl<'de> de::VariantAccess<'de> for TableEnumDeserializer<'de> {
                             e: E::Array(tuple_values),
                             start: self.value.start,
                             end: self.value.end,
-                        }),
+                        })
+                        .with_options(self.options),
                         visitor,
                     )
                 } else {
@@ -962,7 +1636,9 @@ impl<'de> de::VariantAccess<'de> for TableEnumDeserializer<'de> {
         V: de::Visitor<'de>,
     {
         de::Deserializer::deserialize_struct(
-            ValueDeserializer::new(self.value).with_struct_key_validation(),
+            ValueDeserializer::new(self.value)
+                .with_struct_key_validation()
+                .with_options(self.options),
             "", // TODO: this should be the variant name
             fields,
             visitor,
@@ -970,6 +1646,122 @@ impl<'de> de::VariantAccess<'de> for TableEnumDeserializer<'de> {
     }
 }
 
+/// Builds `Deserializer`s with a set of options chosen up front.
+///
+/// Each method sets the option of the same name on `Deserializer`, see there
+/// for what it does. Options which are not set keep the defaults of
+/// `Deserializer::new`. The same builder can build any number of
+/// deserializers.
+///
+/// ```
+/// #[macro_use]
+/// extern crate serde_derive;
+/// extern crate serde;
+/// extern crate toml;
+///
+/// use serde::Deserialize;
+///
+/// #[derive(Deserialize)]
+/// struct Config {
+///     name: String,
+/// }
+///
+/// fn main() {
+///     let mut builder = toml::de::DeserializerBuilder::new();
+///     builder.deny_unknown_fields(true).ignore_key_case(true).max_depth(16);
+///
+///     let config = Config::deserialize(&mut builder.build("Name = 'a'")).unwrap();
+///     assert_eq!(config.name, "a");
+///     assert!(Config::deserialize(&mut builder.build("name = 'a'\nb = 1")).is_err());
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct DeserializerBuilder {
+    require_newline_after_table: bool,
+    allow_duplicate_after_longer_table: bool,
+    deny_unknown_fields: bool,
+    ignore_key_case: bool,
+    coerce_strings: bool,
+    max_depth: usize,
+    normalize_keys: Option<fn(&str) -> String>,
+}
+
+impl DeserializerBuilder {
+    /// Creates a builder with the same options as `Deserializer::new`.
+    pub fn new() -> DeserializerBuilder {
+        DeserializerBuilder {
+            require_newline_after_table: true,
+            allow_duplicate_after_longer_table: false,
+            deny_unknown_fields: false,
+            ignore_key_case: false,
+            coerce_strings: false,
+            max_depth: DEFAULT_MAX_DEPTH,
+            normalize_keys: None,
+        }
+    }
+
+    /// See `Deserializer::set_require_newline_after_table`.
+    pub fn require_newline_after_table(&mut self, require: bool) -> &mut Self {
+        self.require_newline_after_table = require;
+        self
+    }
+
+    /// See `Deserializer::set_allow_duplicate_after_longer_table`.
+    pub fn allow_duplicate_after_longer_table(&mut self, allow: bool) -> &mut Self {
+        self.allow_duplicate_after_longer_table = allow;
+        self
+    }
+
+    /// See `Deserializer::set_deny_unknown_fields`.
+    pub fn deny_unknown_fields(&mut self, deny: bool) -> &mut Self {
+        self.deny_unknown_fields = deny;
+        self
+    }
+
+    /// See `Deserializer::set_ignore_key_case`.
+    pub fn ignore_key_case(&mut self, ignore: bool) -> &mut Self {
+        self.ignore_key_case = ignore;
+        self
+    }
+
+    /// See `Deserializer::set_coerce_strings`.
+    pub fn coerce_strings(&mut self, coerce: bool) -> &mut Self {
+        self.coerce_strings = coerce;
+        self
+    }
+
+    /// See `Deserializer::set_max_depth`.
+    pub fn max_depth(&mut self, depth: usize) -> &mut Self {
+        self.max_depth = depth;
+        self
+    }
+
+    /// See `Deserializer::set_normalize_keys`.
+    pub fn normalize_keys(&mut self, normalize: Option<fn(&str) -> String>) -> &mut Self {
+        self.normalize_keys = normalize;
+        self
+    }
+
+    /// Creates a deserializer for `input` with the options of this builder.
+    pub fn build<'a>(&self, input: &'a str) -> Deserializer<'a> {
+        let mut de = Deserializer::new(input);
+        de.set_require_newline_after_table(self.require_newline_after_table);
+        de.set_allow_duplicate_after_longer_table(self.allow_duplicate_after_longer_table);
+        de.set_deny_unknown_fields(self.deny_unknown_fields);
+        de.set_ignore_key_case(self.ignore_key_case);
+        de.set_coerce_strings(self.coerce_strings);
+        de.set_max_depth(self.max_depth);
+        de.set_normalize_keys(self.normalize_keys);
+        de
+    }
+}
+
+impl Default for DeserializerBuilder {
+    fn default() -> DeserializerBuilder {
+        DeserializerBuilder::new()
+    }
+}
+
 impl<'a> Deserializer<'a> {
     /// Creates a new deserializer which will be deserializing the string
     /// provided.
@@ -979,6 +1771,11 @@ impl<'a> Deserializer<'a> {
             input: input,
             require_newline_after_table: true,
             allow_duplciate_after_longer_table: false,
+            options: ValueOptions::default(),
+            max_depth: DEFAULT_MAX_DEPTH,
+            depth: 0,
+            normalize_keys: None,
+            errors: None,
         }
     }
 
@@ -1010,6 +1807,103 @@ impl<'a> Deserializer<'a> {
         self.allow_duplciate_after_longer_table = allow;
     }
 
+    /// By default keys which don't correspond to a field of the struct being
+    /// deserialized are ignored.
+    ///
+    /// This option can be set to `true` (the default is `false`) to instead
+    /// return an error naming the unexpected key. This applies to structs at
+    /// every level of the document, not just the top-level one, much like
+    /// adding `#[serde(deny_unknown_fields)]` to each of them.
+    pub fn set_deny_unknown_fields(&mut self, deny: bool) {
+        self.options.deny_unknown = deny;
+    }
+
+    /// TOML keys are case-sensitive, so by default `[Server]` will not fill
+    /// in a struct field named `server`.
+    ///
+    /// This option can be set to `true` (the default is `false`) to match
+    /// keys against struct fields regardless of case, which is friendlier
+    /// for hand-written configuration files. A key that matches a field
+    /// exactly is always preferred. If two keys of the same table end up
+    /// matching the same field, for example `name` and `Name`, an error is
+    /// returned rather than picking one of them. Keys of maps are still
+    /// compared exactly.
+    pub fn set_ignore_key_case(&mut self, ignore: bool) {
+        self.options.ignore_case = ignore;
+    }
+
+    /// By default a string is never read into a boolean or number field.
+    ///
+    /// This option can be set to `true` (the default is `false`) to parse
+    /// strings such as `"8080"` or `"true"` when a boolean or number is asked
+    /// for, which helps with configuration sources that turn every value into
+    /// a string, such as environment variables. A string which doesn't parse
+    /// is reported as an invalid value for the key it was found under.
+    pub fn set_coerce_strings(&mut self, coerce: bool) {
+        self.options.coerce_strings = coerce;
+    }
+
+    /// Limits how deeply tables and arrays may be nested in the document.
+    ///
+    /// Every segment of a table header counts as one level, as does every
+    /// table implied by a dotted key and every inline array or inline table.
+    /// For example `[a.b]` is two levels deep, and `c.d = [[1]]` within it
+    /// reaches a depth of five.
+    ///
+    /// Documents which nest deeper than `depth` fail to parse instead of
+    /// running out of stack, which matters when parsing untrusted input. The
+    /// default is 128.
+    pub fn set_max_depth(&mut self, depth: usize) {
+        self.max_depth = depth;
+    }
+
+    /// By default keys are compared code point by code point, as the TOML
+    /// spec requires, so two keys which look the same but are written with
+    /// different Unicode normalization forms, such as a precomposed `é` and
+    /// an `e` followed by a combining accent, are different keys.
+    ///
+    /// This option can be set to a function which normalizes keys, typically
+    /// to NFC, to treat such keys as the same. Every key, whether in a table
+    /// header, a dotted key or an inline table, is passed through it before
+    /// being stored, so deserialized maps and `Value`s hold the normalized
+    /// keys. Keys which only differ before normalization are reported as
+    /// duplicate keys or tables, at the line of the later one. This crate
+    /// doesn't ship Unicode normalization itself, so the function usually
+    /// comes from a crate such as `unicode-normalization`.
+    ///
+    /// ```
+    /// extern crate serde;
+    /// extern crate toml;
+    ///
+    /// use serde::Deserialize;
+    ///
+    /// // Stands in for a full implementation of NFC.
+    /// fn nfc(key: &str) -> String {
+    ///     key.replace("e\u{301}", "\u{e9}")
+    /// }
+    ///
+    /// fn main() {
+    ///     let input = "\"caf\u{e9}\" = 1\n\"cafe\u{301}\" = 2";
+    ///     assert!(input.parse::<toml::Value>().is_ok());
+    ///
+    ///     let mut de = toml::Deserializer::new(input);
+    ///     de.set_normalize_keys(Some(nfc));
+    ///     let err = toml::Value::deserialize(&mut de).unwrap_err();
+    ///     assert_eq!(err.to_string(), "duplicate key `caf\u{e9}`, first defined at line 1 at line 2");
+    /// }
+    /// ```
+    pub fn set_normalize_keys(&mut self, normalize: Option<fn(&str) -> String>) {
+        self.normalize_keys = normalize;
+    }
+
+    fn enter(&mut self, levels: usize, at: usize) -> Result<(), Error> {
+        self.depth += levels;
+        if self.depth > self.max_depth {
+            return Err(self.error(at, ErrorKind::MaxDepthExceeded(self.max_depth)));
+        }
+        Ok(())
+    }
+
     fn tables(&mut self) -> Result<Vec<Table<'a>>, Error> {
         let mut tables = Vec::new();
         let mut cur_table = Table {
@@ -1019,14 +1913,41 @@ impl<'a> Deserializer<'a> {
             array: false,
         };
 
-        while let Some(line) = self.line()? {
+        // Set when recovering from a malformed table header, so that the
+        // key/value pairs below it are dropped rather than misattributed.
+        let mut skip_table = false;
+
+        loop {
+            self.depth = cur_table.header.len();
+            let start = self.tokens.current();
+            let line = match self.line(&cur_table.header) {
+                Ok(Some(line)) => line,
+                Ok(None) => break,
+                Err(e) => {
+                    self.recover(e)?;
+                    // The offending token may have been the newline itself,
+                    // in which case we're already at the start of a new line.
+                    // If nothing was consumed at all, though, the same error
+                    // would come up again forever, so skip the line anyway.
+                    let current = self.tokens.current();
+                    if current == start || !self.input[..current].ends_with('\n') {
+                        self.tokens.skip_to_newline();
+                    }
+                    continue;
+                }
+            };
             match line {
                 Line::Table {
                     at,
                     mut header,
                     array,
                 } => {
-                    if !cur_table.header.is_empty() || cur_table.values.is_some() {
+                    if !skip_table
+                        && (!cur_table.header.is_empty() || cur_table.values.is_some())
+                    {
+                        if let Some(ref mut values) = cur_table.values {
+                            self.remove_duplicate_keys(values)?;
+                        }
                         tables.push(cur_table);
                     }
                     cur_table = Table {
@@ -1035,29 +1956,103 @@ impl<'a> Deserializer<'a> {
                         values: Some(Vec::new()),
                         array: array,
                     };
+                    skip_table = false;
                     loop {
                         let part = header.next().map_err(|e| self.token_error(e));
-                        match part? {
-                            Some(part) => cur_table.header.push(part),
-                            None => break,
+                        match part {
+                            Ok(Some(part)) => cur_table.header.push(self.normalize_key(part)),
+                            Ok(None) => break,
+                            Err(e) => {
+                                self.recover(e)?;
+                                skip_table = true;
+                                break;
+                            }
                         }
                     }
+                    if !skip_table && cur_table.header.len() > self.max_depth {
+                        let kind = ErrorKind::MaxDepthExceeded(self.max_depth);
+                        let e = self.error(at, kind);
+                        self.recover(e)?;
+                        skip_table = true;
+                    }
                 }
+                Line::KeyValue(..) if skip_table => {}
                 Line::KeyValue(key, value) => {
                     if cur_table.values.is_none() {
                         cur_table.values = Some(Vec::new());
                     }
-                    self.add_dotted_key(key, value, cur_table.values.as_mut().unwrap())?;
+                    let res = self.add_dotted_key(key, value, cur_table.values.as_mut().unwrap());
+                    if let Err(e) = res {
+                        self.recover(e)?;
+                    }
                 }
             }
         }
-        if !cur_table.header.is_empty() || cur_table.values.is_some() {
+        if !skip_table && (!cur_table.header.is_empty() || cur_table.values.is_some()) {
+            if let Some(ref mut values) = cur_table.values {
+                self.remove_duplicate_keys(values)?;
+            }
             tables.push(cur_table);
         }
         Ok(tables)
     }
 
-    fn line(&mut self) -> Result<Option<Line<'a>>, Error> {
+    /// Reports keys which are defined more than once in `values`, including
+    /// within dotted tables, and removes all but their first definition.
+    ///
+    /// Keys are sorted rather than compared pairwise so that large tables
+    /// don't take quadratic time.
+    fn remove_duplicate_keys(
+        &mut self,
+        values: &mut Vec<(Cow<'a, str>, Value<'a>)>,
+    ) -> Result<(), Error> {
+        let mut order = (0..values.len()).collect::<Vec<_>>();
+        order.sort_by(|&a, &b| values[a].0.cmp(&values[b].0).then(a.cmp(&b)));
+
+        let mut duplicates = Vec::new();
+        let mut first = 0;
+        for i in 1..order.len() {
+            if values[order[i]].0 == values[order[first]].0 {
+                duplicates.push((order[i], order[first]));
+            } else {
+                first = i;
+            }
+        }
+        duplicates.sort();
+
+        for &(i, first) in duplicates.iter() {
+            let kind = ErrorKind::DuplicateKey {
+                key: values[i].0.to_string(),
+                first: self.to_linecol(values[first].1.start).0,
+            };
+            let e = self.error(values[i].1.start, kind);
+            self.recover(e)?;
+        }
+        for &(i, _) in duplicates.iter().rev() {
+            values.remove(i);
+        }
+
+        for &mut (_, ref mut value) in values.iter_mut() {
+            if let E::DottedTable(ref mut values) = value.e {
+                self.remove_duplicate_keys(values)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Records `e` and returns `Ok` if errors are being collected, otherwise
+    /// returns it as is.
+    fn recover(&mut self, e: Error) -> Result<(), Error> {
+        match self.errors {
+            Some(ref mut errors) => {
+                errors.push(e);
+                Ok(())
+            }
+            None => Err(e),
+        }
+    }
+
+    fn line(&mut self, header: &[Cow<'a, str>]) -> Result<Option<Line<'a>>, Error> {
         loop {
             self.eat_whitespace()?;
             if self.eat_comment()? {
@@ -1071,7 +2066,10 @@ impl<'a> Deserializer<'a> {
 
         match self.peek()? {
             Some((_, Token::LeftBracket)) => self.table_header().map(Some),
-            Some(_) => self.key_value().map(Some),
+            Some(_) => self.key_value().map(Some).map_err(|mut e| {
+                e.add_path_context(header);
+                e
+            }),
             None => Ok(None),
         }
     }
@@ -1106,12 +2104,18 @@ impl<'a> Deserializer<'a> {
     }
 
     fn key_value(&mut self) -> Result<Line<'a>, Error> {
+        let at = self.tokens.current();
         let key = self.dotted_key()?;
         self.eat_whitespace()?;
         self.expect(Token::Equals)?;
         self.eat_whitespace()?;
 
-        let value = self.value()?;
+        self.enter(key.len() - 1, at)?;
+        let value = self.value().map_err(|mut e| {
+            e.add_path_context(&key);
+            e
+        })?;
+        self.depth -= key.len() - 1;
         self.eat_whitespace()?;
         if !self.eat_comment()? {
             self.eat_newline_or_eof()?;
@@ -1141,18 +2145,24 @@ impl<'a> Deserializer<'a> {
             Some((span, Token::Keylike(key))) => self.number_or_date(span, key)?,
             Some((span, Token::Plus)) => self.number_leading_plus(span)?,
             Some((Span { start, .. }, Token::LeftBrace)) => {
-                self.inline_table().map(|(Span { end, .. }, table)| Value {
+                self.enter(1, start)?;
+                let value = self.inline_table().map(|(Span { end, .. }, table)| Value {
                     e: E::InlineTable(table),
                     start: start,
                     end: end,
-                })?
+                })?;
+                self.depth -= 1;
+                value
             }
             Some((Span { start, .. }, Token::LeftBracket)) => {
-                self.array().map(|(Span { end, .. }, array)| Value {
+                self.enter(1, start)?;
+                let value = self.array().map(|(Span { end, .. }, array)| Value {
                     e: E::Array(array),
                     start: start,
                     end: end,
-                })?
+                })?;
+                self.depth -= 1;
+                value
             }
             Some(token) => {
                 return Err(self.error(
@@ -1318,8 +2328,13 @@ impl<'a> Deserializer<'a> {
         if suffix != "" {
             return Err(self.error(start, ErrorKind::NumberInvalid));
         }
-        i64::from_str_radix(&prefix.replace("_", "").trim_start_matches('+'), radix)
-            .map_err(|_e| self.error(start, ErrorKind::NumberInvalid))
+        // The digits were checked above, so this can only fail if the number
+        // doesn't fit. Point at the whole literal including any `0x` prefix.
+        i64::from_str_radix(&prefix.replace("_", "").trim_start_matches('+'), radix).map_err(|_e| {
+            let at = if radix == 10 { start } else { start - 2 };
+            let literal = &self.tokens.input()[at..start + prefix.len()];
+            self.error(at, ErrorKind::IntegerOverflow(literal.to_string()))
+        })
     }
 
     fn parse_integer(
@@ -1494,15 +2509,22 @@ impl<'a> Deserializer<'a> {
             return Ok((span, ret));
         }
         loop {
+            let at = self.tokens.current();
             let key = self.dotted_key()?;
             self.eat_whitespace()?;
             self.expect(Token::Equals)?;
             self.eat_whitespace()?;
-            let value = self.value()?;
+            self.enter(key.len() - 1, at)?;
+            let value = self.value().map_err(|mut e| {
+                e.add_path_context(&key);
+                e
+            })?;
+            self.depth -= key.len() - 1;
             self.add_dotted_key(key, value, &mut ret)?;
 
             self.eat_whitespace()?;
             if let Some(span) = self.eat_spanned(Token::RightBrace)? {
+                self.remove_duplicate_keys(&mut ret)?;
                 return Ok((span, ret));
             }
             self.expect(Token::Comma)?;
@@ -1534,7 +2556,11 @@ impl<'a> Deserializer<'a> {
             let value = self.value()?;
             if let Some(last) = ret.last() {
                 if !value.same_type(last) {
-                    return Err(self.error(at, ErrorKind::MixedArrayType));
+                    let kind = ErrorKind::MixedArrayType {
+                        expected: last.e.type_name(),
+                        found: value.e.type_name(),
+                    };
+                    return Err(self.error(at, kind));
                 }
             }
             ret.push(value);
@@ -1549,10 +2575,19 @@ impl<'a> Deserializer<'a> {
     }
 
     fn table_key(&mut self) -> Result<Cow<'a, str>, Error> {
-        self.tokens
+        let key = self
+            .tokens
             .table_key()
             .map(|t| t.1)
-            .map_err(|e| self.token_error(e))
+            .map_err(|e| self.token_error(e))?;
+        Ok(self.normalize_key(key))
+    }
+
+    fn normalize_key(&self, key: Cow<'a, str>) -> Cow<'a, str> {
+        match self.normalize_keys {
+            Some(normalize) => Cow::Owned(normalize(&key)),
+            None => key,
+        }
     }
 
     fn dotted_key(&mut self) -> Result<Vec<Cow<'a, str>>, Error> {
@@ -1600,8 +2635,12 @@ impl<'a> Deserializer<'a> {
             )) => {
                 return self.add_dotted_key(key_parts, value, v);
             }
-            Some(&mut (_, Value { start, .. })) => {
-                return Err(self.error(start, ErrorKind::DottedKeyInvalidType));
+            Some(&mut (ref key, Value { start, .. })) => {
+                let kind = ErrorKind::DottedKeyInvalidType {
+                    key: key.to_string(),
+                    first: self.to_linecol(start).0,
+                };
+                return Err(self.error(value.start, kind));
             }
             None => {}
         }
@@ -1706,6 +2745,47 @@ impl<'a> Deserializer<'a> {
         }
     }
 
+    /// Returns the key to hand to serde for `key`, which is the name of the
+    /// struct field it matched if `fields` are being checked at all.
+    ///
+    /// `matched` remembers which keys were matched to which fields so far, in
+    /// order to catch two keys matching the same field when ignoring case.
+    fn field_key(
+        &self,
+        fields: Option<&'static [&'static str]>,
+        matched: &mut Vec<(&'static str, Cow<'a, str>)>,
+        key: Cow<'a, str>,
+        at: usize,
+    ) -> Result<Cow<'a, str>, Error> {
+        let fields = match fields {
+            Some(fields) => fields,
+            None => return Ok(key),
+        };
+        let field = match self.options.find(fields, &key) {
+            Some(field) => field,
+            None if self.options.deny_unknown => {
+                let kind = ErrorKind::UnexpectedKeys {
+                    keys: vec![key.into_owned()],
+                    available: fields,
+                };
+                return Err(self.error(at, kind));
+            }
+            None => return Ok(key),
+        };
+        if self.options.ignore_case {
+            if let Some(&(_, ref first)) = matched.iter().find(|m| m.0 == field && m.1 != key) {
+                let kind = ErrorKind::DuplicateKeyIgnoringCase {
+                    field: field,
+                    first: first.to_string(),
+                    second: key.into_owned(),
+                };
+                return Err(self.error(at, kind));
+            }
+            matched.push((field, key));
+        }
+        Ok(Cow::Borrowed(field))
+    }
+
     fn error(&self, at: usize, kind: ErrorKind) -> Error {
         let mut err = Error::from_kind(kind);
         let (line, col) = self.to_linecol(at);
@@ -1716,28 +2796,236 @@ impl<'a> Deserializer<'a> {
 
     /// Converts a byte offset from an error message to a (line, column) pair
     ///
-    /// All indexes are 0-based.
+    /// All indexes are 0-based, and the column counts characters rather than
+    /// bytes.
     fn to_linecol(&self, offset: usize) -> (usize, usize) {
-        let mut cur = 0;
-        for (i, line) in self.input.lines().enumerate() {
-            if cur + line.len() + 1 > offset {
-                return (i, offset - cur);
-            }
-            cur += line.len() + 1;
+        let mut offset = cmp::min(offset, self.input.len());
+        while !self.input.is_char_boundary(offset) {
+            offset -= 1;
         }
-        (self.input.lines().count(), 0)
+        let before = &self.input[..offset];
+        let line = before.matches('\n').count();
+        let start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+        (line, before[start..].chars().count())
     }
 }
 
 impl Error {
     /// Produces a (line, column) pair of the position of the error if available
     ///
-    /// All indexes are 0-based.
+    /// All indexes are 0-based, and the column is measured in characters, so
+    /// multi-byte UTF-8 text before the error only counts once per character.
     pub fn line_col(&self) -> Option<(usize, usize)> {
         self.inner.line.map(|line| (line, self.inner.col))
     }
 
-    fn from_kind(kind: ErrorKind) -> Error {
+    /// Returns the table header and keys which were being parsed when a
+    /// syntax error occurred, outermost first.
+    ///
+    /// This is empty for errors at the top level of the document, in a table
+    /// header, and for errors raised while deserializing.
+    ///
+    /// ```
+    /// let input = "[servers.alpha]\noptions = { retries = @ }";
+    /// let err = input.parse::<toml::Value>().unwrap_err();
+    /// assert_eq!(err.path(), ["servers", "alpha", "options", "retries"]);
+    /// ```
+    pub fn path(&self) -> &[String] {
+        &self.inner.path
+    }
+
+    /// Returns the expected and found types if this error was caused by a
+    /// value of the wrong type.
+    ///
+    /// The expected type is described by the type being deserialized, while
+    /// the found type is the name of a TOML type such as `"string"`.
+    ///
+    /// ```
+    /// #[macro_use]
+    /// extern crate serde_derive;
+    /// extern crate toml;
+    ///
+    /// #[derive(Debug, Deserialize)]
+    /// struct Config {
+    ///     port: u16,
+    /// }
+    ///
+    /// fn main() {
+    ///     let err = toml::from_str::<Config>("port = '8080'").unwrap_err();
+    ///     assert_eq!(err.type_mismatch(), Some(("u16", "string")));
+    ///     assert_eq!(
+    ///         err.to_string(),
+    ///         "invalid type: string \"8080\", expected u16 for key `port`"
+    ///     );
+    /// }
+    /// ```
+    pub fn type_mismatch(&self) -> Option<(&str, &str)> {
+        match self.inner.kind {
+            ErrorKind::TypeMismatch {
+                ref expected,
+                found,
+            } => Some((expected, found)),
+            _ => None,
+        }
+    }
+
+    /// Tells whether this error came from parsing the TOML document or from
+    /// deserializing the parsed document into a type.
+    ///
+    /// `from_str` does both in one step, so this is how to tell a malformed
+    /// document apart from one which is merely missing something.
+    ///
+    /// ```
+    /// use toml::de::Category;
+    ///
+    /// let err = toml::from_str::<u32>("a = @").unwrap_err();
+    /// assert_eq!(err.classify(), Category::Syntax);
+    ///
+    /// let err = toml::from_str::<u32>("a = 1").unwrap_err();
+    /// assert_eq!(err.classify(), Category::Data);
+    /// ```
+    pub fn classify(&self) -> Category {
+        match self.inner.kind {
+            ErrorKind::UnexpectedEof
+            | ErrorKind::InvalidCharInString(_)
+            | ErrorKind::InvalidEscape(_)
+            | ErrorKind::InvalidHexEscape(_)
+            | ErrorKind::InvalidEscapeValue(_)
+            | ErrorKind::NewlineInString
+            | ErrorKind::Unexpected(_)
+            | ErrorKind::UnterminatedString
+            | ErrorKind::NewlineInTableKey
+            | ErrorKind::NumberInvalid
+            | ErrorKind::IntegerOverflow(_)
+            | ErrorKind::DateInvalid
+            | ErrorKind::MixedArrayType { .. }
+            | ErrorKind::DuplicateTable { .. }
+            | ErrorKind::DuplicateKey { .. }
+            | ErrorKind::RedefineAsArray
+            | ErrorKind::EmptyTableKey
+            | ErrorKind::MultilineStringKey
+            | ErrorKind::DottedKeyInvalidType { .. }
+            | ErrorKind::InputTooLong(_) => Category::Syntax,
+
+            // These are raised both while parsing and while deserializing,
+            // and only the parser knows where in the document it is.
+            ErrorKind::Wanted { .. } | ErrorKind::MaxDepthExceeded(_) | ErrorKind::Custom => {
+                if self.inner.line.is_some() {
+                    Category::Syntax
+                } else {
+                    Category::Data
+                }
+            }
+
+            ErrorKind::ExpectedTuple(_)
+            | ErrorKind::ExpectedTupleIndex { .. }
+            | ErrorKind::ArrayLength { .. }
+            | ErrorKind::TypeMismatch { .. }
+            | ErrorKind::ExpectedEmptyTable
+            | ErrorKind::UnexpectedKeys { .. }
+            | ErrorKind::DuplicateKeyIgnoringCase { .. }
+            | ErrorKind::__Nonexhaustive => Category::Data,
+        }
+    }
+
+    /// Returns a code telling what kind of mistake caused this error.
+    ///
+    /// ```
+    /// use toml::de::Code;
+    ///
+    /// let err = "a = 1\na = 2".parse::<toml::Value>().unwrap_err();
+    /// assert_eq!(err.code(), Code::DuplicateKey);
+    ///
+    /// let err = "a = 1979-05-27T07:".parse::<toml::Value>().unwrap_err();
+    /// assert_eq!(err.code(), Code::InvalidDatetime);
+    /// ```
+    pub fn code(&self) -> Code {
+        match self.inner.kind {
+            ErrorKind::UnexpectedEof | ErrorKind::Unexpected(_) => Code::UnexpectedToken,
+            ErrorKind::Wanted { .. } if self.inner.line.is_some() => Code::UnexpectedToken,
+            ErrorKind::InvalidCharInString(_)
+            | ErrorKind::InvalidEscape(_)
+            | ErrorKind::InvalidHexEscape(_)
+            | ErrorKind::InvalidEscapeValue(_)
+            | ErrorKind::NewlineInString
+            | ErrorKind::UnterminatedString => Code::InvalidString,
+            ErrorKind::NewlineInTableKey
+            | ErrorKind::EmptyTableKey
+            | ErrorKind::MultilineStringKey => Code::InvalidKey,
+            ErrorKind::NumberInvalid | ErrorKind::IntegerOverflow(_) => Code::InvalidNumber,
+            ErrorKind::DateInvalid => Code::InvalidDatetime,
+            ErrorKind::MixedArrayType { .. } => Code::MixedArray,
+            ErrorKind::DuplicateKey { .. } | ErrorKind::DottedKeyInvalidType { .. } => {
+                Code::DuplicateKey
+            }
+            ErrorKind::DuplicateTable { .. } | ErrorKind::RedefineAsArray => {
+                Code::InvalidTableHeader
+            }
+            ErrorKind::MaxDepthExceeded(_) | ErrorKind::InputTooLong(_) => Code::LimitExceeded,
+            ErrorKind::Wanted { .. }
+            | ErrorKind::Custom
+            | ErrorKind::ExpectedTuple(_)
+            | ErrorKind::ExpectedTupleIndex { .. }
+            | ErrorKind::ArrayLength { .. }
+            | ErrorKind::TypeMismatch { .. }
+            | ErrorKind::ExpectedEmptyTable
+            | ErrorKind::UnexpectedKeys { .. }
+            | ErrorKind::DuplicateKeyIgnoringCase { .. }
+            | ErrorKind::__Nonexhaustive => Code::Other,
+        }
+    }
+
+    /// Renders this error along with the line of `input` it occurred on,
+    /// with a caret pointing at the offending column.
+    ///
+    /// `input` should be the document this error was produced from. Tabs in
+    /// the line are expanded to four spaces, and a byte order mark at the
+    /// start of the document is left out, so the caret stays aligned. If
+    /// the error has no position, or the position is not within `input`, only
+    /// the message is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let input = "a = 1\nb = @";
+    /// let err = input.parse::<toml::Value>().unwrap_err();
+    ///
+    /// assert_eq!(
+    ///     err.render(input),
+    ///     "unexpected character found: `@` at line 2\n  |\n2 | b = @\n  |     ^\n"
+    /// );
+    /// ```
+    pub fn render(&self, input: &str) -> String {
+        let (line, col) = match self.line_col() {
+            Some(pos) => pos,
+            None => return self.to_string(),
+        };
+        let source = match input.lines().nth(line) {
+            Some(source) => source,
+            None => return self.to_string(),
+        };
+
+        let expand = |c: char| match c {
+            '\t' => 4,
+            '\u{feff}' => 0,
+            _ => 1,
+        };
+        let text = source.replace('\t', "    ").replace('\u{feff}', "");
+        let offset = source.chars().take(col).map(expand).sum::<usize>();
+        let number = (line + 1).to_string();
+        let gutter = " ".repeat(number.len());
+        format!(
+            "{}\n{} |\n{} | {}\n{} | {}^\n",
+            self,
+            gutter,
+            number,
+            text,
+            gutter,
+            " ".repeat(offset)
+        )
+    }
+
+    pub(crate) fn from_kind(kind: ErrorKind) -> Error {
         Error {
             inner: Box::new(ErrorInner {
                 kind: kind,
@@ -1745,6 +3033,7 @@ impl Error {
                 col: 0,
                 message: String::new(),
                 key: Vec::new(),
+                path: Vec::new(),
             }),
         }
     }
@@ -1757,10 +3046,16 @@ impl Error {
                 col: 0,
                 message: s,
                 key: Vec::new(),
+                path: Vec::new(),
             }),
         }
     }
 
+    fn add_path_context(&mut self, path: &[Cow<str>]) {
+        let path = path.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+        self.inner.path.splice(0..0, path);
+    }
+
     /// Do not call this method, it may be removed at any time, it's just an
     /// internal implementation detail.
     #[doc(hidden)]
@@ -1801,11 +3096,29 @@ impl fmt::Display for Error {
                 write!(f, "expected {}, found {}", expected, found)?
             }
             ErrorKind::NumberInvalid => "invalid number".fmt(f)?,
+            ErrorKind::IntegerOverflow(ref literal) => write!(
+                f,
+                "integer `{}` is out of range of a 64-bit signed integer",
+                literal
+            )?,
             ErrorKind::DateInvalid => "invalid date".fmt(f)?,
-            ErrorKind::MixedArrayType => "mixed types in an array".fmt(f)?,
-            ErrorKind::DuplicateTable(ref s) => {
-                write!(f, "redefinition of table `{}`", s)?;
-            }
+            ErrorKind::MixedArrayType { expected, found } => write!(
+                f,
+                "mixed types in an array, expected {} but found {}",
+                expected, found
+            )?,
+            ErrorKind::DuplicateTable { ref name, first } => write!(
+                f,
+                "redefinition of table `{}`, first defined at line {}",
+                name,
+                first + 1
+            )?,
+            ErrorKind::DuplicateKey { ref key, first } => write!(
+                f,
+                "duplicate key `{}`, first defined at line {}",
+                key,
+                first + 1
+            )?,
             ErrorKind::RedefineAsArray => "table redefined as array".fmt(f)?,
             ErrorKind::EmptyTableKey => "empty table key found".fmt(f)?,
             ErrorKind::MultilineStringKey => "multiline strings are not allowed for key".fmt(f)?,
@@ -1815,9 +3128,32 @@ impl fmt::Display for Error {
                 expected,
                 ref found,
             } => write!(f, "expected table key `{}`, but was `{}`", expected, found)?,
+            ErrorKind::ArrayLength { expected, found } => write!(
+                f,
+                "array has too {} elements, expected {} but found {}",
+                if found < expected { "few" } else { "many" },
+                expected,
+                found
+            )?,
+            ErrorKind::TypeMismatch { ref expected, .. } => write!(
+                f,
+                "invalid type: {}, expected {}",
+                self.inner.message, expected
+            )?,
             ErrorKind::ExpectedEmptyTable => "expected empty table".fmt(f)?,
-            ErrorKind::DottedKeyInvalidType => {
-                "dotted key attempted to extend non-table type".fmt(f)?
+            ErrorKind::DottedKeyInvalidType { ref key, first } => write!(
+                f,
+                "dotted key attempted to extend non-table `{}`, first defined at line {}",
+                key,
+                first + 1
+            )?,
+            ErrorKind::MaxDepthExceeded(max) => write!(
+                f,
+                "tables and arrays are nested deeper than the maximum of {}",
+                max
+            )?,
+            ErrorKind::InputTooLong(max) => {
+                write!(f, "input is longer than the maximum of {} bytes", max)?
             }
             ErrorKind::UnexpectedKeys {
                 ref keys,
@@ -1827,6 +3163,15 @@ impl fmt::Display for Error {
                 "unexpected keys in table: `{:?}`, available keys: `{:?}`",
                 keys, available
             )?,
+            ErrorKind::DuplicateKeyIgnoringCase {
+                field,
+                ref first,
+                ref second,
+            } => write!(
+                f,
+                "keys `{}` and `{}` both match field `{}` when ignoring case",
+                first, second, field
+            )?,
             ErrorKind::__Nonexhaustive => panic!(),
         }
 
@@ -1863,18 +3208,25 @@ impl error::Error for Error {
             ErrorKind::NewlineInTableKey => "found newline in table key",
             ErrorKind::Wanted { .. } => "expected a token but found another",
             ErrorKind::NumberInvalid => "invalid number",
+            ErrorKind::IntegerOverflow(_) => "integer out of range",
             ErrorKind::DateInvalid => "invalid date",
-            ErrorKind::MixedArrayType => "mixed types in an array",
-            ErrorKind::DuplicateTable(_) => "duplicate table",
+            ErrorKind::MixedArrayType { .. } => "mixed types in an array",
+            ErrorKind::DuplicateTable { .. } => "duplicate table",
+            ErrorKind::DuplicateKey { .. } => "duplicate key",
             ErrorKind::RedefineAsArray => "table redefined as array",
             ErrorKind::EmptyTableKey => "empty table key found",
             ErrorKind::MultilineStringKey => "invalid multiline string for key",
             ErrorKind::Custom => "a custom error",
             ErrorKind::ExpectedTuple(_) => "expected table length",
             ErrorKind::ExpectedTupleIndex { .. } => "expected table key",
+            ErrorKind::ArrayLength { .. } => "wrong array length",
+            ErrorKind::TypeMismatch { .. } => "invalid type",
             ErrorKind::ExpectedEmptyTable => "expected empty table",
-            ErrorKind::DottedKeyInvalidType => "dotted key invalid type",
+            ErrorKind::DottedKeyInvalidType { .. } => "dotted key invalid type",
+            ErrorKind::MaxDepthExceeded(_) => "nesting too deep",
+            ErrorKind::InputTooLong(_) => "input too long",
             ErrorKind::UnexpectedKeys { .. } => "unexpected keys in table",
+            ErrorKind::DuplicateKeyIgnoringCase { .. } => "duplicate key ignoring case",
             ErrorKind::__Nonexhaustive => panic!(),
         }
     }
@@ -1884,6 +3236,28 @@ impl de::Error for Error {
     fn custom<T: fmt::Display>(msg: T) -> Error {
         Error::custom(msg.to_string())
     }
+
+    fn invalid_type(unexp: de::Unexpected, exp: &de::Expected) -> Error {
+        let mut err = Error::from_kind(ErrorKind::TypeMismatch {
+            expected: exp.to_string(),
+            found: type_name(&unexp),
+        });
+        err.inner.message = unexp.to_string();
+        err
+    }
+}
+
+/// Names the TOML type of a value serde reports as unexpected.
+fn type_name(unexp: &de::Unexpected) -> &'static str {
+    match *unexp {
+        de::Unexpected::Bool(_) => "boolean",
+        de::Unexpected::Unsigned(_) | de::Unexpected::Signed(_) => "integer",
+        de::Unexpected::Float(_) => "float",
+        de::Unexpected::Char(_) | de::Unexpected::Str(_) => "string",
+        de::Unexpected::Seq => "array",
+        de::Unexpected::Map => "table",
+        _ => "value",
+    }
 }
 
 enum Line<'a> {
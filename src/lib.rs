@@ -55,6 +55,12 @@
 //! traits; the example above uses `FromStr` to parse a `str` into a
 //! `Value`.
 //!
+//! Note that a `Value` only holds data. Comments, whitespace and the
+//! original formatting of a document are discarded while parsing, so
+//! serializing a parsed document will not reproduce them. Tools which need
+//! to edit a document in place while keeping its comments intact need a
+//! format-preserving parser rather than this crate.
+//!
 //! ## Deserialization and Serialization
 //!
 //! This crate supports [`serde`] 1.0 with a number of
@@ -68,6 +74,7 @@
 //! * `Deserializer for de::Deserializer`
 //! * `Serializer for ser::Serializer`
 //! * `Deserializer for Value`
+//! * `Deserializer for &Value`
 //!
 //! This means that you can use Serde to deserialize/serialize the
 //! `Value` type as well as the `Datetime` type in this crate. You can also
@@ -110,6 +117,54 @@
 //! }
 //! ```
 //!
+//! Keys which a struct does not declare are ignored by default. To keep them
+//! around, for example to warn about typos in a configuration file, collect
+//! them into a map with `#[serde(flatten)]`:
+//!
+//! ```rust
+//! #[macro_use]
+//! extern crate serde_derive;
+//! extern crate toml;
+//!
+//! use std::collections::BTreeMap;
+//! use toml::Value;
+//!
+//! #[derive(Deserialize)]
+//! struct Config {
+//!     ip: String,
+//!     #[serde(flatten)]
+//!     extra: BTreeMap<String, Value>,
+//! }
+//!
+//! fn main() {
+//!     let config: Config = toml::from_str(r#"
+//!         ip = '127.0.0.1'
+//!         timout = 30
+//!     "#).unwrap();
+//!
+//!     assert_eq!(config.ip, "127.0.0.1");
+//!     assert_eq!(config.extra["timout"].as_integer(), Some(30));
+//! }
+//! ```
+//!
+//! A table which is left out of the document entirely is a missing field
+//! like any other, and serde reports it as an error even when every field of
+//! the struct it would fill in is optional. Mark such fields with
+//! `#[serde(default)]`, and derive or implement `Default` for their type, to
+//! get an empty struct instead.
+//!
+//! Keys in TOML are always strings, but maps such as `HashMap<u16, String>`
+//! can be deserialized too: when a map asks for an integer or boolean key, the
+//! key is parsed with the `FromStr` implementation of that type. A key which
+//! fails to parse is reported as an invalid value.
+//!
+//! Fields of [`chrono`] types such as `DateTime<Utc>`, `NaiveDateTime` and
+//! `NaiveDate` can be read straight from TOML datetimes. A datetime which
+//! doesn't fit the field, such as a local datetime read into a
+//! `DateTime<Utc>`, is reported along with the datetime itself. Serializing
+//! these fields writes them out as strings; use `toml::value::Datetime` for
+//! fields which must be written back as TOML datetimes.
+//!
 //! You can serialize types in a similar fashion:
 //!
 //! ```rust
@@ -147,6 +202,7 @@
 //! [TOML]: https://github.com/toml-lang/toml
 //! [Cargo]: https://crates.io/
 //! [`serde`]: https://serde.rs/
+//! [`chrono`]: https://docs.rs/chrono
 
 #![doc(html_root_url = "https://docs.rs/toml/0.4")]
 #![deny(missing_docs)]
@@ -156,6 +212,8 @@ extern crate chrono;
 extern crate serde;
 #[cfg(feature = "preserve_order")]
 extern crate linked_hash_map;
+#[cfg(feature = "serde_json")]
+extern crate serde_json;
 
 pub mod map;
 pub mod value;
@@ -168,7 +226,7 @@ pub mod ser;
 pub use ser::{to_string, to_string_pretty, to_vec, Serializer};
 pub mod de;
 #[doc(no_inline)]
-pub use de::{from_slice, from_str, Deserializer};
+pub use de::{from_reader, from_reader_with_max_len, from_slice, from_str, validate, Deserializer};
 mod tokens;
 
 #[doc(hidden)]
@@ -31,6 +31,25 @@ use value::{Array, Table, Value};
 ///     println!("{:#?}", cargo_toml);
 /// }
 /// ```
+///
+/// The input is tokenized by the Rust compiler, so only TOML which is also a
+/// sequence of valid Rust tokens is accepted. The following is supported:
+///
+/// * `key = value` pairs, including dotted keys like `a.b = 1`. Keys may
+///   contain hyphens and may be quoted, like `"cfg(windows)" = true`.
+/// * Table headers like `[package]` and array of tables headers like
+///   `[[bin]]`.
+/// * Basic strings in double quotes, as well as Rust raw strings like
+///   `r"C:\Users"` in place of TOML literal strings.
+/// * Integers, including `0x`, `0o` and `0b` prefixes and `_` separators,
+///   and floats, including `inf` and `nan`.
+/// * Booleans, offset and local date-times, local dates and local times.
+/// * Inline arrays and inline tables, nested arbitrarily.
+///
+/// Comments, single-quoted literal strings and multi-line strings have no
+/// Rust token equivalent and are not supported. Values must be literals;
+/// use `Value::from` and the indexing operators to splice in Rust
+/// expressions after the fact.
 #[macro_export]
 macro_rules! toml {
     ($($toml:tt)+) => {{
@@ -17,9 +17,10 @@
 use serde::{de, ser};
 use std::fmt::{self, Debug};
 use value::Value;
-use std::hash::Hash;
+use std::hash::{Hash, Hasher};
 use std::iter::FromIterator;
 use std::borrow::Borrow;
+use std::cmp::Ordering;
 use std::ops;
 
 #[cfg(not(feature = "preserve_order"))]
@@ -118,9 +119,16 @@ impl Map<String, Value> {
     ///
     /// If the map did have this key present, the value is updated, and the old
     /// value is returned. The key is not updated, though; this matters for
-    /// types that can be `==` without being identical.
+    /// types that can be `==` without being identical. With `preserve_order`
+    /// the key also keeps its place in the map.
     #[inline]
     pub fn insert(&mut self, k: String, v: Value) -> Option<Value> {
+        #[cfg(feature = "preserve_order")]
+        {
+            if let Some(old) = self.map.get_mut(&k) {
+                return Some(::std::mem::replace(old, v));
+            }
+        }
         self.map.insert(k, v)
     }
 
@@ -225,6 +233,26 @@ impl PartialEq for Map<String, Value> {
     }
 }
 
+/// Maps hash their entries in iteration order, matching how they are compared.
+impl Hash for Map<String, Value> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_usize(self.len());
+        for (key, value) in self.iter() {
+            key.hash(state);
+            value.hash(state);
+        }
+    }
+}
+
+/// Maps are compared entry by entry in iteration order, which is key order
+/// unless the `preserve_order` feature is enabled.
+impl PartialOrd for Map<String, Value> {
+    #[inline]
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.iter().partial_cmp(other.iter())
+    }
+}
+
 /// Access an element of this map. Panics if the given key is not present in the
 /// map.
 impl<'a, Q: ?Sized> ops::Index<&'a Q> for Map<String, Value>
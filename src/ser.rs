@@ -5,11 +5,12 @@
 //! are also provided at the top of the crate.
 //!
 //! Note that the TOML format has a restriction that if a table itself contains
-//! tables, all keys with non-table values must be emitted first. This is
-//! typically easy to ensure happens when you're defining a `struct` as you can
-//! reorder the fields manually, but when working with maps (such as `BTreeMap`
-//! or `HashMap`) this can lead to serialization errors. In those situations you
-//! may use the `tables_last` function in this module like so:
+//! tables, all keys with non-table values must be emitted first. The
+//! serializer takes care of this for structs and maps by writing a value which
+//! comes after a table in front of the tables written so far. To write the
+//! entries of a map in that order in the first place, for example to keep the
+//! order they're written in predictable, you may use the `tables_last`
+//! function in this module like so:
 //!
 //! ```rust
 //! # #[macro_use] extern crate serde_derive;
@@ -53,6 +54,9 @@ where
 /// fail, if `T` contains a map with non-string keys, or if `T` attempts to
 /// serialize an unsupported datatype such as an enum, tuple, or tuple struct.
 ///
+/// Fields and map entries whose value is `None` are left out of the output
+/// altogether, since TOML has no null value.
+///
 /// # Examples
 ///
 /// ```
@@ -100,6 +104,14 @@ where
 ///
 /// This is identical to `to_string` except the output string has a more
 /// "pretty" output. See `Serializer::pretty` for more details.
+///
+/// # Examples
+///
+/// ```
+/// let value: toml::Value = "ports = [80, 443]".parse().unwrap();
+/// let toml = toml::to_string_pretty(&value).unwrap();
+/// assert_eq!(toml, "ports = [\n    80,\n    443,\n]\n");
+/// ```
 pub fn to_string_pretty<T: ?Sized>(value: &T) -> Result<String, Error>
 where
     T: ser::Serialize,
@@ -121,18 +133,27 @@ pub enum Error {
 
     /// The key of all TOML maps must be strings, but serialization was
     /// attempted where the key of a map was not a string.
+    ///
+    /// Booleans, integers and characters are written out as strings, which
+    /// are parsed back when deserializing, so only other kinds of keys cause
+    /// this error.
     KeyNotString,
 
     /// An error that we never omit but keep for backwards compatibility
     #[doc(hidden)]
     KeyNewline,
 
+    /// A map key was empty. TOML can only spell an empty key as `""`, which
+    /// isn't accepted when parsing, so such keys aren't emitted either.
+    KeyEmpty,
+
     /// Arrays in TOML must have a homogenous type, but a heterogeneous array
     /// was emitted.
     ArrayMixedType,
 
     /// All values in a TOML table must be emitted before further tables are
-    /// emitted. If a value is emitted *after* a table then this error is
+    /// emitted. Structs and maps are reordered to make sure this is the case,
+    /// but if a value is still emitted *after* a table then this error is
     /// generated.
     ValueAfterTable,
 
@@ -190,6 +211,8 @@ impl StringSettings {
 struct Settings {
     array: Option<ArraySettings>,
     string: Option<StringSettings>,
+    integer_group: Option<usize>,
+    dotted_keys: bool,
 }
 
 /// Serialization implementation for TOML.
@@ -232,6 +255,20 @@ pub struct SerializeSeq<'a: 'b, 'b> {
     len: Option<usize>,
 }
 
+#[doc(hidden)]
+pub struct SerializeTupleVariant<'a: 'b, 'b> {
+    ser: &'b mut Serializer<'a>,
+    variant: &'static str,
+    fields: Vec<::value::Value>,
+}
+
+#[doc(hidden)]
+pub struct SerializeStructVariant<'a: 'b, 'b> {
+    ser: &'b mut Serializer<'a>,
+    variant: &'static str,
+    fields: ::value::Table,
+}
+
 #[doc(hidden)]
 pub enum SerializeTable<'a: 'b, 'b> {
     Datetime(&'b mut Serializer<'a>),
@@ -240,9 +277,18 @@ pub enum SerializeTable<'a: 'b, 'b> {
         key: String,
         first: Cell<bool>,
         table_emitted: Cell<bool>,
+        tables: Option<Tables>,
     },
 }
 
+/// Where the nested tables of a table start in the output, so values which
+/// come after them can be moved in front of them.
+#[doc(hidden)]
+pub struct Tables {
+    at: usize,
+    header_pending: bool,
+}
+
 impl<'a> Serializer<'a> {
     /// Creates a new serializer which will emit TOML into the buffer provided.
     ///
@@ -271,6 +317,8 @@ impl<'a> Serializer<'a> {
             settings: Rc::new(Settings {
                 array: Some(ArraySettings::pretty()),
                 string: Some(StringSettings::pretty()),
+                integer_group: None,
+                dotted_keys: false,
             }),
         }
     }
@@ -426,6 +474,82 @@ impl<'a> Serializer<'a> {
         self
     }
 
+    /// Group the digits of integers with underscores
+    ///
+    /// If `value` is non-zero, integers with more than `value` digits have an
+    /// underscore inserted every `value` digits, counting from the right. A
+    /// value of 0 (the default) disables grouping. This is not enabled by
+    /// `Serializer::pretty`.
+    ///
+    /// # Examples
+    ///
+    /// With a group size of 3, instead of:
+    ///
+    /// ```toml,ignore
+    /// population = 7800000000
+    /// ```
+    ///
+    /// You will have:
+    ///
+    /// ```toml,ignore
+    /// population = 7_800_000_000
+    /// ```
+    pub fn pretty_integer_group(&mut self, value: usize) -> &mut Self {
+        Rc::get_mut(&mut self.settings).unwrap().integer_group =
+            if value > 0 { Some(value) } else { None };
+        self
+    }
+
+    /// Enable or disable writing single-key tables as dotted keys.
+    ///
+    /// If `value` is true, a table which holds exactly one key is written as
+    /// a dotted key in its parent instead of under a header of its own. This
+    /// also applies to chains of such tables, as long as the value at the end
+    /// of the chain holds no tables. This is not enabled by
+    /// `Serializer::pretty`.
+    ///
+    /// # Examples
+    ///
+    /// Instead of:
+    ///
+    /// ```toml,ignore
+    /// [package]
+    /// name = "toml"
+    ///
+    /// [package.metadata.docs]
+    /// all-features = true
+    /// ```
+    ///
+    /// You will have:
+    ///
+    /// ```toml,ignore
+    /// [package]
+    /// name = "toml"
+    /// metadata.docs.all-features = true
+    /// ```
+    pub fn dotted_keys(&mut self, value: bool) -> &mut Self {
+        Rc::get_mut(&mut self.settings).unwrap().dotted_keys = value;
+        self
+    }
+
+    fn display_integer<T: fmt::Display>(&mut self, t: T) -> Result<(), Error> {
+        let size = match self.settings.integer_group {
+            Some(size) => size,
+            None => return self.display(t, "integer"),
+        };
+
+        let repr = t.to_string();
+        let digits = repr.trim_start_matches('-');
+        let mut grouped = repr[..repr.len() - digits.len()].to_string();
+        for (i, c) in digits.chars().enumerate() {
+            if i > 0 && (digits.len() - i) % size == 0 {
+                grouped.push('_');
+            }
+            grouped.push(c);
+        }
+        self.display(grouped, "integer")
+    }
+
     fn display<T: fmt::Display>(&mut self, t: T, type_: &'static str) -> Result<(), Error> {
         self.emit_key(type_)?;
         drop(write!(self.dst, "{}", t));
@@ -516,6 +640,9 @@ impl<'a> Serializer<'a> {
     }
 
     fn escape_key(&mut self, key: &str) -> Result<(), Error> {
+        if key.is_empty() {
+            return Err(Error::KeyEmpty);
+        }
         let ok = key.chars().all(|c| match c {
             'a'...'z' | 'A'...'Z' | '0'...'9' | '-' | '_' => true,
             _ => false,
@@ -538,22 +665,18 @@ impl<'a> Serializer<'a> {
 
         enum Repr {
             /// represent as a literal string (using '')
-            Literal(String, Type),
+            Literal(Type),
             /// represent the std way (using "")
             Std(Type),
         }
 
         fn do_pretty(value: &str) -> Repr {
-            // For doing pretty prints we store in a new String
-            // because there are too many cases where pretty cannot
-            // work. We need to determine:
+            // There are too many cases where pretty cannot work, so scan the
+            // string first. A literal string is written out verbatim, so the
+            // scan doesn't need to copy it anywhere. We need to determine:
             // - if we are a "multi-line" pretty (if there are \n)
             // - if ['''] appears if multi or ['] if single
             // - if there are any invalid control characters
-            //
-            // Doing it any other way would require multiple passes
-            // to determine if a pretty string works or not.
-            let mut out = String::with_capacity(value.len() * 2);
             let mut ty = Type::OnelineSingle;
             // found consecutive single quotes
             let mut max_found_singles = 0;
@@ -577,10 +700,9 @@ impl<'a> Serializer<'a> {
                         '\t' => {}
                         '\n' => ty = Type::NewlineTripple,
                         // note that the following are invalid: \b \f \r
-                        c if c < '\u{1f}' => can_be_pretty = false, // Invalid control character
+                        c if c <= '\u{1f}' || c == '\u{7f}' => can_be_pretty = false, // Invalid control character
                         _ => {}
                     }
-                    out.push(ch);
                 } else {
                     // the string cannot be represented as pretty,
                     // still check if it should be multiline
@@ -605,28 +727,26 @@ impl<'a> Serializer<'a> {
                 // no newlines, but must use ''' because it has ' in it
                 ty = Type::OnelineTripple;
             }
-            Repr::Literal(out, ty)
+            Repr::Literal(ty)
         }
 
         let repr = if !is_key && self.settings.string.is_some() {
             match (&self.settings.string, do_pretty(value)) {
-                (&Some(StringSettings { literal: false, .. }), Repr::Literal(_, ty)) => {
-                    Repr::Std(ty)
-                }
+                (&Some(StringSettings { literal: false, .. }), Repr::Literal(ty)) => Repr::Std(ty),
                 (_, r @ _) => r,
             }
         } else {
             Repr::Std(Type::OnelineSingle)
         };
         match repr {
-            Repr::Literal(literal, ty) => {
+            Repr::Literal(ty) => {
                 // A pretty string
                 match ty {
                     Type::NewlineTripple => self.dst.push_str("'''\n"),
                     Type::OnelineTripple => self.dst.push_str("'''"),
                     Type::OnelineSingle => self.dst.push('\''),
                 }
-                self.dst.push_str(&literal);
+                self.dst.push_str(value);
                 match ty {
                     Type::OnelineSingle => self.dst.push('\''),
                     _ => self.dst.push_str("'''"),
@@ -653,7 +773,9 @@ impl<'a> Serializer<'a> {
                         '\u{d}' => self.dst.push_str("\\r"),
                         '\u{22}' => self.dst.push_str("\\\""),
                         '\u{5c}' => self.dst.push_str("\\\\"),
-                        c if c < '\u{1f}' => drop(write!(self.dst, "\\u{:04X}", ch as u32)),
+                        c if c <= '\u{1f}' || c == '\u{7f}' => {
+                            drop(write!(self.dst, "\\u{:04X}", ch as u32))
+                        }
                         ch => self.dst.push(ch),
                     }
                 }
@@ -696,27 +818,21 @@ impl<'a> Serializer<'a> {
             }
         }
 
-        match *state {
-            State::Table { first, .. } => {
-                if !first.get() {
-                    // Newline if we are a table that is not the first
-                    // table in the document.
-                    self.dst.push('\n');
-                }
-            }
-            State::Array { parent, first, .. } => {
-                if !first.get() {
-                    // Always newline if we are not the first item in the
-                    // table-array
-                    self.dst.push('\n');
-                } else if let State::Table { first, .. } = *parent {
+        // Newline if anything has been emitted before this header. That may
+        // have been in an enclosing table whose own header was left out, so
+        // look all the way up rather than only at the nearest table.
+        let mut p = state;
+        loop {
+            match *p {
+                State::Table { first, parent, .. } | State::Array { first, parent, .. } => {
                     if !first.get() {
-                        // Newline if we are not the first item in the document
                         self.dst.push('\n');
+                        break;
                     }
+                    p = parent;
                 }
+                State::End => break,
             }
-            _ => {}
         }
         self.dst.push_str("[");
         if array_of_tables {
@@ -755,16 +871,33 @@ impl<'a> Serializer<'a> {
 macro_rules! serialize_float {
     ($this:expr, $v:expr) => {{
         $this.emit_key("float")?;
-        if ($v.is_nan() || $v == 0.0) && $v.is_sign_negative() {
-            drop(write!($this.dst, "-"));
-        }
         if $v.is_nan() {
+            if $v.is_sign_negative() {
+                drop(write!($this.dst, "-"));
+            }
             drop(write!($this.dst, "nan"));
+        } else if $v == 0.0 {
+            // Not all versions of Rust print the sign of a negative zero.
+            if $v.is_sign_negative() {
+                drop(write!($this.dst, "-"));
+            }
+            drop(write!($this.dst, "0.0"));
         } else {
-            drop(write!($this.dst, "{}", $v));
-        }
-        if $v % 1.0 == 0.0 {
-            drop(write!($this.dst, ".0"));
+            // Both formats print the shortest digits which parse back to the
+            // same value, but very large or small numbers would otherwise be
+            // written out with hundreds of zeroes.
+            let abs = $v.abs();
+            let repr = if abs.is_finite() && (abs >= 1e16 || abs < 1e-5) {
+                format!("{:e}", $v)
+            } else {
+                format!("{}", $v)
+            };
+            // Make sure the value isn't read back as an integer.
+            let is_integral = repr.bytes().all(|b| b == b'-' || b.is_ascii_digit());
+            drop(write!($this.dst, "{}", repr));
+            if is_integral {
+                drop(write!($this.dst, ".0"));
+            }
         }
         if let State::Table { .. } = $this.state {
             $this.dst.push_str("\n");
@@ -779,45 +912,45 @@ impl<'a, 'b> ser::Serializer for &'b mut Serializer<'a> {
     type SerializeSeq = SerializeSeq<'a, 'b>;
     type SerializeTuple = SerializeSeq<'a, 'b>;
     type SerializeTupleStruct = SerializeSeq<'a, 'b>;
-    type SerializeTupleVariant = SerializeSeq<'a, 'b>;
+    type SerializeTupleVariant = SerializeTupleVariant<'a, 'b>;
     type SerializeMap = SerializeTable<'a, 'b>;
     type SerializeStruct = SerializeTable<'a, 'b>;
-    type SerializeStructVariant = ser::Impossible<(), Error>;
+    type SerializeStructVariant = SerializeStructVariant<'a, 'b>;
 
     fn serialize_bool(self, v: bool) -> Result<(), Self::Error> {
         self.display(v, "bool")
     }
 
     fn serialize_i8(self, v: i8) -> Result<(), Self::Error> {
-        self.display(v, "integer")
+        self.display_integer(v)
     }
 
     fn serialize_i16(self, v: i16) -> Result<(), Self::Error> {
-        self.display(v, "integer")
+        self.display_integer(v)
     }
 
     fn serialize_i32(self, v: i32) -> Result<(), Self::Error> {
-        self.display(v, "integer")
+        self.display_integer(v)
     }
 
     fn serialize_i64(self, v: i64) -> Result<(), Self::Error> {
-        self.display(v, "integer")
+        self.display_integer(v)
     }
 
     fn serialize_u8(self, v: u8) -> Result<(), Self::Error> {
-        self.display(v, "integer")
+        self.display_integer(v)
     }
 
     fn serialize_u16(self, v: u16) -> Result<(), Self::Error> {
-        self.display(v, "integer")
+        self.display_integer(v)
     }
 
     fn serialize_u32(self, v: u32) -> Result<(), Self::Error> {
-        self.display(v, "integer")
+        self.display_integer(v)
     }
 
     fn serialize_u64(self, v: u64) -> Result<(), Self::Error> {
-        self.display(v, "integer")
+        self.display_integer(v)
     }
 
     fn serialize_f32(self, v: f32) -> Result<(), Self::Error> {
@@ -862,8 +995,9 @@ impl<'a, 'b> ser::Serializer for &'b mut Serializer<'a> {
         Err(Error::UnsupportedType)
     }
 
-    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), Self::Error> {
-        Err(Error::UnsupportedType)
+    // TOML has no unit type, so unit structs are written as empty tables.
+    fn serialize_unit_struct(self, name: &'static str) -> Result<(), Self::Error> {
+        ser::SerializeStruct::end(self.serialize_struct(name, 0)?)
     }
 
     fn serialize_unit_variant(
@@ -890,13 +1024,19 @@ impl<'a, 'b> ser::Serializer for &'b mut Serializer<'a> {
         self,
         _name: &'static str,
         _variant_index: u32,
-        _variant: &'static str,
-        _value: &T,
+        variant: &'static str,
+        value: &T,
     ) -> Result<(), Self::Error>
     where
         T: ser::Serialize,
     {
-        Err(Error::UnsupportedType)
+        use serde::ser::SerializeMap;
+
+        // Newtype variants are emitted as a table with a single key, the name
+        // of the variant, which is the form the deserializer reads back.
+        let mut map = self.serialize_map(Some(1))?;
+        map.serialize_entry(variant, value)?;
+        map.end()
     }
 
     fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
@@ -925,10 +1065,14 @@ impl<'a, 'b> ser::Serializer for &'b mut Serializer<'a> {
         self,
         _name: &'static str,
         _variant_index: u32,
-        _variant: &'static str,
+        variant: &'static str,
         len: usize,
     ) -> Result<Self::SerializeTupleVariant, Self::Error> {
-        self.serialize_seq(Some(len))
+        Ok(SerializeTupleVariant {
+            ser: self,
+            variant: variant,
+            fields: Vec::with_capacity(len),
+        })
     }
 
     fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
@@ -938,6 +1082,7 @@ impl<'a, 'b> ser::Serializer for &'b mut Serializer<'a> {
             key: String::new(),
             first: Cell::new(true),
             table_emitted: Cell::new(false),
+            tables: None,
         })
     }
 
@@ -956,6 +1101,7 @@ impl<'a, 'b> ser::Serializer for &'b mut Serializer<'a> {
                 key: String::new(),
                 first: Cell::new(true),
                 table_emitted: Cell::new(false),
+                tables: None,
             })
         }
     }
@@ -964,10 +1110,14 @@ impl<'a, 'b> ser::Serializer for &'b mut Serializer<'a> {
         self,
         _name: &'static str,
         _variant_index: u32,
-        _variant: &'static str,
+        variant: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeStructVariant, Self::Error> {
-        Err(Error::UnsupportedType)
+        Ok(SerializeStructVariant {
+            ser: self,
+            variant: variant,
+            fields: ::value::Table::new(),
+        })
     }
 }
 
@@ -1036,22 +1186,6 @@ impl<'a, 'b> ser::SerializeTuple for SerializeSeq<'a, 'b> {
     }
 }
 
-impl<'a, 'b> ser::SerializeTupleVariant for SerializeSeq<'a, 'b> {
-    type Ok = ();
-    type Error = Error;
-
-    fn serialize_field<T: ?Sized>(&mut self, value: &T) -> Result<(), Error>
-    where
-        T: ser::Serialize,
-    {
-        ser::SerializeSeq::serialize_element(self, value)
-    }
-
-    fn end(self) -> Result<(), Error> {
-        ser::SerializeSeq::end(self)
-    }
-}
-
 impl<'a, 'b> ser::SerializeTupleStruct for SerializeSeq<'a, 'b> {
     type Ok = ();
     type Error = Error;
@@ -1097,23 +1231,9 @@ impl<'a, 'b> ser::SerializeMap for SerializeTable<'a, 'b> {
                 ref key,
                 ref first,
                 ref table_emitted,
-                ..
+                ref mut tables,
             } => {
-                let res = value.serialize(&mut Serializer {
-                    dst: &mut *ser.dst,
-                    state: State::Table {
-                        key: key,
-                        parent: &ser.state,
-                        first: first,
-                        table_emitted: table_emitted,
-                    },
-                    settings: ser.settings.clone(),
-                });
-                match res {
-                    Ok(()) => first.set(false),
-                    Err(Error::UnsupportedNone) => {}
-                    Err(e) => return Err(e),
-                }
+                serialize_table_value(ser, key, value, first, table_emitted, tables)?;
             }
         }
         Ok(())
@@ -1153,23 +1273,10 @@ impl<'a, 'b> ser::SerializeStruct for SerializeTable<'a, 'b> {
                 ref mut ser,
                 ref first,
                 ref table_emitted,
+                ref mut tables,
                 ..
             } => {
-                let res = value.serialize(&mut Serializer {
-                    dst: &mut *ser.dst,
-                    state: State::Table {
-                        key: key,
-                        parent: &ser.state,
-                        first: first,
-                        table_emitted: table_emitted,
-                    },
-                    settings: ser.settings.clone(),
-                });
-                match res {
-                    Ok(()) => first.set(false),
-                    Err(Error::UnsupportedNone) => {}
-                    Err(e) => return Err(e),
-                }
+                serialize_table_value(ser, key, value, first, table_emitted, tables)?;
             }
         }
         Ok(())
@@ -1189,6 +1296,222 @@ impl<'a, 'b> ser::SerializeStruct for SerializeTable<'a, 'b> {
     }
 }
 
+// Serializes the value of a key of a table which is being written out by
+// `ser`.
+//
+// TOML requires the plain values of a table to come before its nested
+// tables, but structs and maps hand their entries over in whatever order
+// they like. So once a nested table has been written, a later plain value
+// is written in front of all of the nested tables instead, by taking them
+// off the end of the output and putting them back afterwards.
+fn serialize_table_value<T: ?Sized>(
+    ser: &mut Serializer,
+    key: &str,
+    value: &T,
+    first: &Cell<bool>,
+    table_emitted: &Cell<bool>,
+    tables: &mut Option<Tables>,
+) -> Result<(), Error>
+where
+    T: ser::Serialize,
+{
+    let start = ser.dst.len();
+    let was_first = first.get();
+    let mut res = serialize_table_entry(ser, key, value, first, table_emitted);
+    if let Err(Error::ValueAfterTable) = res {
+        if let Some(ref mut t) = *tables {
+            ser.dst.truncate(start);
+            let moved = ser.dst.split_off(t.at);
+            if t.header_pending {
+                first.set(true);
+            }
+            table_emitted.set(false);
+            res = serialize_table_entry(ser, key, value, first, table_emitted);
+            table_emitted.set(true);
+            t.at = ser.dst.len();
+            t.header_pending = false;
+            if res.is_ok() && !moved.starts_with('\n') {
+                ser.dst.push('\n');
+            }
+            ser.dst.push_str(&moved);
+        }
+    }
+    match res {
+        Ok(()) => first.set(false),
+        Err(Error::UnsupportedNone) => {}
+        Err(e) => return Err(e),
+    }
+
+    if tables.is_none() && table_emitted.get() {
+        let mut t = Tables {
+            at: start,
+            header_pending: was_first,
+        };
+        // The header of this table is only written along with its first
+        // value, unless it's an element of an array of tables. Then it was
+        // written just before the nested table, and values go after it.
+        if let State::Array { .. } = ser.state {
+            if was_first {
+                let text = &ser.dst[start..];
+                let skip = if text.starts_with('\n') { 1 } else { 0 };
+                t.at += text[skip..].find('\n').map_or(text.len(), |i| skip + i + 1);
+                t.header_pending = false;
+            }
+        }
+        *tables = Some(t);
+    }
+    Ok(())
+}
+
+fn serialize_table_entry<T: ?Sized>(
+    ser: &mut Serializer,
+    key: &str,
+    value: &T,
+    first: &Cell<bool>,
+    table_emitted: &Cell<bool>,
+) -> Result<(), Error>
+where
+    T: ser::Serialize,
+{
+    if ser.settings.dotted_keys {
+        if let Some((keys, leaf)) = dotted_value(value) {
+            use serde::ser::Serialize;
+            if table_emitted.get() {
+                return Err(Error::ValueAfterTable);
+            }
+            if first.get() {
+                let state = ser.state.clone();
+                ser.emit_table_header(&state)?;
+                first.set(false);
+            }
+            ser.escape_key(key)?;
+            for key in keys.iter() {
+                ser.dst.push('.');
+                ser.escape_key(key)?;
+            }
+            ser.dst.push_str(" = ");
+            leaf.serialize(&mut Serializer {
+                dst: &mut *ser.dst,
+                state: State::End,
+                settings: ser.settings.clone(),
+            })?;
+            ser.dst.push('\n');
+            return Ok(());
+        }
+    }
+
+    value.serialize(&mut Serializer {
+        dst: &mut *ser.dst,
+        state: State::Table {
+            key: key,
+            parent: &ser.state,
+            first: first,
+            table_emitted: table_emitted,
+        },
+        settings: ser.settings.clone(),
+    })
+}
+
+// If `value` is a chain of tables which each hold exactly one key, ending in
+// something other than a table, returns the keys of the chain along with the
+// value at its end.
+fn dotted_value<T: ?Sized>(value: &T) -> Option<(Vec<String>, ::value::Value)>
+where
+    T: ser::Serialize,
+{
+    use value::Value;
+
+    match value.serialize(Categorize::<Error>::new()) {
+        Ok(Category::Table) => {}
+        _ => return None,
+    }
+    let mut keys = Vec::new();
+    let mut value = Value::try_from(value).ok()?;
+    loop {
+        let (key, next) = match value {
+            Value::Table(table) => {
+                if table.len() != 1 {
+                    return None;
+                }
+                let (key, value) = table.into_iter().next().unwrap();
+                if key == datetime::FIELD {
+                    // A datetime converted to a `Value` comes out as the
+                    // table it is serialized as, so turn it back.
+                    let date = value.as_str()?.parse().ok()?;
+                    return dotted_leaf(keys, Value::Datetime(date));
+                }
+                (key, value)
+            }
+            value => return dotted_leaf(keys, value),
+        };
+        keys.push(key);
+        value = next;
+    }
+}
+
+fn dotted_leaf(keys: Vec<String>, value: ::value::Value) -> Option<(Vec<String>, ::value::Value)> {
+    fn has_table(value: &::value::Value) -> bool {
+        match *value {
+            ::value::Value::Table(_) => true,
+            ::value::Value::Array(ref a) => a.iter().any(has_table),
+            _ => false,
+        }
+    }
+
+    if keys.is_empty() || has_table(&value) {
+        return None;
+    }
+    Some((keys, value))
+}
+
+impl<'a, 'b> ser::SerializeTupleVariant for SerializeTupleVariant<'a, 'b> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: ser::Serialize,
+    {
+        self.fields.push(::value::Value::try_from(value)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<(), Error> {
+        // Like struct variants, the fields are emitted as an array under a
+        // table keyed by the variant name.
+        let mut table = ::value::Table::new();
+        table.insert(self.variant.to_string(), ::value::Value::Array(self.fields));
+        ser::Serialize::serialize(&::value::Value::Table(table), self.ser)
+    }
+}
+
+impl<'a, 'b> ser::SerializeStructVariant for SerializeStructVariant<'a, 'b> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), Error>
+    where
+        T: ser::Serialize,
+    {
+        match ::value::Value::try_from(value) {
+            Ok(value) => {
+                self.fields.insert(key.to_string(), value);
+            }
+            Err(Error::UnsupportedNone) => {}
+            Err(e) => return Err(e),
+        }
+        Ok(())
+    }
+
+    fn end(self) -> Result<(), Error> {
+        // The fields are buffered so that they can be emitted as the body of
+        // a table keyed by the variant name, with values sorted before tables.
+        let mut table = ::value::Table::new();
+        table.insert(self.variant.to_string(), ::value::Value::Table(self.fields));
+        ser::Serialize::serialize(&::value::Value::Table(table), self.ser)
+    }
+}
+
 struct DateStrEmitter<'a: 'b, 'b>(&'b mut Serializer<'a>);
 
 impl<'a, 'b> ser::Serializer for DateStrEmitter<'a, 'b> {
@@ -1373,40 +1696,40 @@ impl ser::Serializer for StringExtractor {
     type SerializeStruct = ser::Impossible<String, Error>;
     type SerializeStructVariant = ser::Impossible<String, Error>;
 
-    fn serialize_bool(self, _v: bool) -> Result<String, Self::Error> {
-        Err(Error::KeyNotString)
+    fn serialize_bool(self, v: bool) -> Result<String, Self::Error> {
+        Ok(v.to_string())
     }
 
-    fn serialize_i8(self, _v: i8) -> Result<String, Self::Error> {
-        Err(Error::KeyNotString)
+    fn serialize_i8(self, v: i8) -> Result<String, Self::Error> {
+        Ok(v.to_string())
     }
 
-    fn serialize_i16(self, _v: i16) -> Result<String, Self::Error> {
-        Err(Error::KeyNotString)
+    fn serialize_i16(self, v: i16) -> Result<String, Self::Error> {
+        Ok(v.to_string())
     }
 
-    fn serialize_i32(self, _v: i32) -> Result<String, Self::Error> {
-        Err(Error::KeyNotString)
+    fn serialize_i32(self, v: i32) -> Result<String, Self::Error> {
+        Ok(v.to_string())
     }
 
-    fn serialize_i64(self, _v: i64) -> Result<String, Self::Error> {
-        Err(Error::KeyNotString)
+    fn serialize_i64(self, v: i64) -> Result<String, Self::Error> {
+        Ok(v.to_string())
     }
 
-    fn serialize_u8(self, _v: u8) -> Result<String, Self::Error> {
-        Err(Error::KeyNotString)
+    fn serialize_u8(self, v: u8) -> Result<String, Self::Error> {
+        Ok(v.to_string())
     }
 
-    fn serialize_u16(self, _v: u16) -> Result<String, Self::Error> {
-        Err(Error::KeyNotString)
+    fn serialize_u16(self, v: u16) -> Result<String, Self::Error> {
+        Ok(v.to_string())
     }
 
-    fn serialize_u32(self, _v: u32) -> Result<String, Self::Error> {
-        Err(Error::KeyNotString)
+    fn serialize_u32(self, v: u32) -> Result<String, Self::Error> {
+        Ok(v.to_string())
     }
 
-    fn serialize_u64(self, _v: u64) -> Result<String, Self::Error> {
-        Err(Error::KeyNotString)
+    fn serialize_u64(self, v: u64) -> Result<String, Self::Error> {
+        Ok(v.to_string())
     }
 
     fn serialize_f32(self, _v: f32) -> Result<String, Self::Error> {
@@ -1417,8 +1740,8 @@ impl ser::Serializer for StringExtractor {
         Err(Error::KeyNotString)
     }
 
-    fn serialize_char(self, _v: char) -> Result<String, Self::Error> {
-        Err(Error::KeyNotString)
+    fn serialize_char(self, v: char) -> Result<String, Self::Error> {
+        Ok(v.to_string())
     }
 
     fn serialize_str(self, value: &str) -> Result<String, Self::Error> {
@@ -1535,6 +1858,7 @@ impl fmt::Display for Error {
         match *self {
             Error::UnsupportedType => "unsupported Rust type".fmt(f),
             Error::KeyNotString => "map key was not a string".fmt(f),
+            Error::KeyEmpty => "map key was empty".fmt(f),
             Error::ArrayMixedType => "arrays cannot have mixed types".fmt(f),
             Error::ValueAfterTable => "values must be emitted before tables".fmt(f),
             Error::DateInvalid => "a serialized date was invalid".fmt(f),
@@ -1552,6 +1876,7 @@ impl error::Error for Error {
         match *self {
             Error::UnsupportedType => "unsupported Rust type",
             Error::KeyNotString => "map key was not a string",
+            Error::KeyEmpty => "map key was empty",
             Error::ArrayMixedType => "arrays cannot have mixed types",
             Error::ValueAfterTable => "values must be emitted before tables",
             Error::DateInvalid => "a serialized date was invalid",
@@ -1713,7 +2038,7 @@ impl<E: ser::Error> ser::Serializer for Categorize<E> {
     }
 
     fn serialize_unit_struct(self, _: &'static str) -> Result<Self::Ok, Self::Error> {
-        Err(ser::Error::custom("unsupported"))
+        Ok(Category::Table)
     }
 
     fn serialize_unit_variant(
@@ -1823,7 +2148,7 @@ impl<E: ser::Error> ser::SerializeTupleVariant for Categorize<E> {
     }
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
-        Ok(Category::Array)
+        Ok(Category::Table)
     }
 }
 
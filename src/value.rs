@@ -1,9 +1,15 @@
 //! Definition of a TOML value
 
+use std::cmp;
 use std::collections::{BTreeMap, HashMap};
+use std::error;
+use std::f64;
 use std::fmt;
-use std::hash::Hash;
+use std::hash::{Hash, Hasher};
+use std::iter;
+use std::mem;
 use std::ops;
+use std::slice;
 use std::str::FromStr;
 use std::vec;
 
@@ -12,13 +18,23 @@ use serde::de::IntoDeserializer;
 use serde::ser;
 
 use datetime::{self, DatetimeFromString};
-pub use datetime::{Datetime, DatetimeParseError};
+use de::ErrorKind;
+pub use datetime::{Datetime, DatetimeKind, DatetimeParseError};
 
 pub use map::Map;
+use map::{self, Entry};
 
 
 /// Representation of a TOML value.
-#[derive(PartialEq, Clone, Debug)]
+///
+/// Values are ordered first by type, in the order the variants are declared
+/// here, so every string sorts before every integer and so on. Integers and
+/// floats are never equal to each other, even when numerically the same. Two
+/// values of the same type compare by their contents: strings
+/// lexicographically, numbers numerically, datetimes chronologically, arrays
+/// element by element and tables entry by entry in iteration order. Floats
+/// and anything containing a NaN may be unordered, just like `f64`.
+#[derive(PartialEq, PartialOrd, Clone, Debug)]
 pub enum Value {
     /// Represents a TOML string
     String(String),
@@ -66,6 +82,12 @@ impl Value {
     /// something is wrong with the data, for example required struct fields are
     /// missing from the TOML map or some number is too big to fit in the expected
     /// primitive type.
+    ///
+    /// Deserialization fails rather than risking a stack overflow once arrays
+    /// and tables are nested more than 128 levels deep, the same limit that
+    /// applies when parsing a document. This holds for every way of
+    /// deserializing from a `Value` or `&Value`. Use `try_into_with_max_depth`
+    /// to pick another limit.
     pub fn try_into<'de, T>(self) -> Result<T, ::de::Error>
     where
         T: de::Deserialize<'de>,
@@ -73,6 +95,54 @@ impl Value {
         de::Deserialize::deserialize(self)
     }
 
+    /// Like `try_into`, but rejects values whose arrays and tables are nested
+    /// more than `max_depth` levels deep.
+    ///
+    /// Depth is counted the same way as by `Deserializer::set_max_depth`: the
+    /// outermost value is at depth zero and every array or table inside it
+    /// adds one level.
+    ///
+    /// ```
+    /// let value: toml::Value = "a = [[1]]".parse().unwrap();
+    /// assert!(value.clone().try_into_with_max_depth::<toml::Value>(2).is_ok());
+    /// assert!(value.try_into_with_max_depth::<toml::Value>(1).is_err());
+    /// ```
+    pub fn try_into_with_max_depth<'de, T>(self, max_depth: usize) -> Result<T, ::de::Error>
+    where
+        T: de::Deserialize<'de>,
+    {
+        de::Deserialize::deserialize(ValueDeserializer {
+            value: self,
+            depth: Depth::new(max_depth),
+        })
+    }
+
+    /// Parses a TOML document into a `Value::Table`, reporting every error in
+    /// the document rather than only the first.
+    ///
+    /// This is `str::parse` for documents which may contain several
+    /// mistakes. Errors are collected the same way as by
+    /// `de::from_str_recover`, and are returned in the order they occurred.
+    ///
+    /// ```
+    /// let value = toml::Value::parse("a = 1\n[b]\nc = 'x'").unwrap();
+    /// assert_eq!(value["b"]["c"].as_str(), Some("x"));
+    ///
+    /// let errors = toml::Value::parse("a = @\nb = 2\nc = =").unwrap_err();
+    /// assert_eq!(errors.len(), 2);
+    /// ```
+    pub fn parse(s: &str) -> Result<Value, Vec<::de::Error>> {
+        match ::de::from_str_recover(s) {
+            (Some(value), ref errors) if errors.is_empty() => Ok(value),
+            // Redefined tables are only found once the whole document has
+            // been parsed, so put their errors back in source order.
+            (_, mut errors) => {
+                errors.sort_by_key(|e| e.line_col());
+                Err(errors)
+            }
+        }
+    }
+
     /// Index into a TOML array or map. A string index can be used to access a
     /// value in a map, and a usize index can be used to access an element of an
     /// array.
@@ -81,6 +151,14 @@ impl Value {
     /// index, for example if the index is a string and `self` is an array or a
     /// number. Also returns `None` if the given key does not exist in the map
     /// or the given index is not within the bounds of the array.
+    ///
+    /// ```
+    /// let value: toml::Value = "a = [1, 2]".parse().unwrap();
+    ///
+    /// assert_eq!(value.get("a").and_then(|a| a.get(1)), Some(&2.into()));
+    /// assert_eq!(value.get("b"), None);
+    /// assert_eq!(value.get(0), None);
+    /// ```
     pub fn get<I: Index>(&self, index: I) -> Option<&Value> {
         index.index(self)
     }
@@ -97,7 +175,245 @@ impl Value {
         index.index_mut(self)
     }
 
+    /// Looks up a value by a dotted path such as `"servers.alpha.ip"`.
+    ///
+    /// Each segment of the path indexes a table by key, or an array by a
+    /// numeric index, so `"deps.0.name"` is the `name` key of the first
+    /// element of the `deps` array. An empty path returns `self`.
+    ///
+    /// Returns `None` as soon as a segment is missing or does not match the
+    /// type of the value it is applied to. Keys which themselves contain a
+    /// `.` cannot be reached through this method, use `get` instead.
+    pub fn lookup(&self, path: &str) -> Option<&Value> {
+        if path.is_empty() {
+            return Some(self);
+        }
+        let mut cur = self;
+        for key in path.split('.') {
+            cur = match *cur {
+                Value::Table(ref t) => t.get(key)?,
+                Value::Array(ref a) => a.get(key.parse::<usize>().ok()?)?,
+                _ => return None,
+            };
+        }
+        Some(cur)
+    }
+
+    /// Looks up a value by a dotted path like `lookup`, returning `default`
+    /// if there is no value at that path.
+    ///
+    /// Both `self` and `default` are borrowed for as long as the returned
+    /// reference is used, so the default has to outlive it, usually by being
+    /// bound to a variable rather than built inline.
+    ///
+    /// ```
+    /// let value: toml::Value = "[server]\nport = 8080".parse().unwrap();
+    /// let default = toml::Value::from(80);
+    ///
+    /// assert_eq!(value.get_path_or("server.port", &default).as_integer(), Some(8080));
+    /// assert_eq!(value.get_path_or("server.tls.port", &default).as_integer(), Some(80));
+    /// ```
+    pub fn get_path_or<'a>(&'a self, path: &str, default: &'a Value) -> &'a Value {
+        self.lookup(path).unwrap_or(default)
+    }
+
+    /// Mutably looks up a value by a dotted path such as `"servers.alpha.ip"`.
+    ///
+    /// Paths are interpreted the same way as in `lookup`, and `None` is
+    /// returned under the same conditions, including array indices that are
+    /// out of bounds.
+    pub fn lookup_mut(&mut self, path: &str) -> Option<&mut Value> {
+        if path.is_empty() {
+            return Some(self);
+        }
+        let mut cur = self;
+        for key in path.split('.') {
+            cur = match *cur {
+                Value::Table(ref mut t) => t.get_mut(key)?,
+                Value::Array(ref mut a) => a.get_mut(key.parse::<usize>().ok()?)?,
+                _ => return None,
+            };
+        }
+        Some(cur)
+    }
+
+    /// Looks up a value by a JSON Pointer ([RFC 6901]) such as
+    /// `"/servers/alpha/ip"`.
+    ///
+    /// Each `/`-prefixed token indexes a table by key, or an array by a
+    /// decimal index without leading zeros. Within a token `~1` stands for `/`
+    /// and `~0` for `~`, so unlike `lookup` every key can be reached, including
+    /// ones containing dots or slashes. An empty pointer returns `self`.
+    ///
+    /// Returns `None` if the pointer doesn't start with `/`, or if a token is
+    /// missing or does not match the type of the value it is applied to.
+    ///
+    /// ```
+    /// let value: toml::Value = r#"
+    ///     [servers."alpha.example.com"]
+    ///     ports = [80, 443]
+    ///     "a/b" = true
+    /// "#.parse().unwrap();
+    ///
+    /// let server = value.pointer("/servers/alpha.example.com").unwrap();
+    /// assert_eq!(server.pointer("/ports/1"), Some(&443.into()));
+    /// assert_eq!(server.pointer("/a~1b"), Some(&true.into()));
+    /// assert_eq!(server.pointer("/ports/2"), None);
+    /// ```
+    ///
+    /// [RFC 6901]: https://tools.ietf.org/html/rfc6901
+    pub fn pointer(&self, pointer: &str) -> Option<&Value> {
+        if pointer.is_empty() {
+            return Some(self);
+        }
+        if !pointer.starts_with('/') {
+            return None;
+        }
+        let mut cur = self;
+        for token in pointer[1..].split('/') {
+            let token = token.replace("~1", "/").replace("~0", "~");
+            cur = match *cur {
+                Value::Table(ref t) => t.get(&token)?,
+                Value::Array(ref a) => a.get(pointer_index(&token)?)?,
+                _ => return None,
+            };
+        }
+        Some(cur)
+    }
+
+    /// Mutably looks up a value by a JSON Pointer such as
+    /// `"/servers/alpha/ip"`.
+    ///
+    /// Pointers are interpreted the same way as in `pointer`, and `None` is
+    /// returned under the same conditions.
+    pub fn pointer_mut(&mut self, pointer: &str) -> Option<&mut Value> {
+        if pointer.is_empty() {
+            return Some(self);
+        }
+        if !pointer.starts_with('/') {
+            return None;
+        }
+        let mut cur = self;
+        for token in pointer[1..].split('/') {
+            let token = token.replace("~1", "/").replace("~0", "~");
+            cur = match *cur {
+                Value::Table(ref mut t) => t.get_mut(&token)?,
+                Value::Array(ref mut a) => a.get_mut(pointer_index(&token)?)?,
+                _ => return None,
+            };
+        }
+        Some(cur)
+    }
+
+    /// Removes the value at a dotted path such as `"servers.alpha"` and
+    /// returns it.
+    ///
+    /// Paths are interpreted the same way as in `lookup`. The value is removed
+    /// from its parent table, or from its parent array, in which case the
+    /// elements after it shift down by one. Returns `None` and leaves `self`
+    /// untouched if there is no value at `path`, or if `path` is empty.
+    ///
+    /// ```
+    /// let mut value: toml::Value = "a = { b = 1, c = [2, 3] }".parse().unwrap();
+    /// assert_eq!(value.take("a.b"), Some(toml::Value::Integer(1)));
+    /// assert_eq!(value.take("a.c.0"), Some(toml::Value::Integer(2)));
+    /// assert_eq!(value.to_string(), "[a]\nc = [3]\n");
+    /// ```
+    pub fn take(&mut self, path: &str) -> Option<Value> {
+        if path.is_empty() {
+            return None;
+        }
+        let (parent, key) = match path.rfind('.') {
+            Some(i) => (&path[..i], &path[i + 1..]),
+            None => ("", path),
+        };
+        match *self.lookup_mut(parent)? {
+            Value::Table(ref mut t) => t.remove(key),
+            Value::Array(ref mut a) => {
+                let index = key.parse::<usize>().ok()?;
+                if index < a.len() {
+                    Some(a.remove(index))
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Removes `key` from this table and returns its value.
+    ///
+    /// Unlike `take`, the key is not interpreted as a path, so it may contain
+    /// `.`. Returns `None` if `self` is not a table or has no such key.
+    ///
+    /// ```
+    /// let mut value: toml::Value = "a = 1\n'b.c' = 2".parse().unwrap();
+    /// assert_eq!(value.remove("b.c"), Some(toml::Value::Integer(2)));
+    /// assert_eq!(value.remove("b.c"), None);
+    /// assert_eq!(value.to_string(), "a = 1\n");
+    /// ```
+    pub fn remove(&mut self, key: &str) -> Option<Value> {
+        match *self {
+            Value::Table(ref mut t) => t.remove(key),
+            _ => None,
+        }
+    }
+
+    /// Sets the value at a dotted path such as `"servers.alpha.ip"`, returning
+    /// the value previously there, if any.
+    ///
+    /// Every segment of the path but the last names a key of a table. Tables
+    /// which don't exist yet are created along the way. If `self` or any value
+    /// along the path exists but is not a table, an error is returned and
+    /// `self` is left as it was. Unlike `lookup`, arrays are never indexed
+    /// into. An empty path replaces `self` entirely.
+    ///
+    /// ```
+    /// let mut value = toml::Value::Table(toml::value::Table::new());
+    /// value.insert("server.port", toml::Value::from(80)).unwrap();
+    /// let old = value.insert("server.port", toml::Value::from(8080)).unwrap();
+    /// assert_eq!(old, Some(toml::Value::from(80)));
+    /// assert_eq!(value.to_string(), "[server]\nport = 8080\n");
+    ///
+    /// assert!(value.insert("server.port.tls", toml::Value::from(true)).is_err());
+    /// ```
+    pub fn insert(&mut self, path: &str, value: Value) -> Result<Option<Value>, InsertError> {
+        if path.is_empty() {
+            return Ok(Some(mem::replace(self, value)));
+        }
+        // Check the whole path first so that nothing is created on failure.
+        let mut cur = Some(&*self);
+        let mut walked = Vec::new();
+        for key in path.split('.') {
+            match cur {
+                Some(&Value::Table(ref t)) => cur = t.get(key),
+                Some(other) => return Err(InsertError::new(walked.join("."), other)),
+                None => break,
+            }
+            walked.push(key);
+        }
+
+        let mut keys = path.split('.');
+        let last = keys.next_back().unwrap();
+        let mut cur = self;
+        for key in keys {
+            cur = match *cur {
+                Value::Table(ref mut t) => t
+                    .entry(key.to_string())
+                    .or_insert_with(|| Value::Table(Table::new())),
+                _ => unreachable!(),
+            };
+        }
+        match *cur {
+            Value::Table(ref mut t) => Ok(t.insert(last.to_string(), value)),
+            _ => unreachable!(),
+        }
+    }
+
     /// Extracts the integer value if it is an integer.
+    ///
+    /// Floats are never converted, even if they have no fractional part; see
+    /// `as_number_f64` for an accessor which accepts both.
     pub fn as_integer(&self) -> Option<i64> {
         match *self {
             Value::Integer(i) => Some(i),
@@ -111,6 +427,8 @@ impl Value {
     }
 
     /// Extracts the float value if it is a float.
+    ///
+    /// Integers are not converted; see `as_number_f64` for that.
     pub fn as_float(&self) -> Option<f64> {
         match *self {
             Value::Float(f) => Some(f),
@@ -118,6 +436,26 @@ impl Value {
         }
     }
 
+    /// Extracts the value as an `f64` if it is either a float or an integer.
+    ///
+    /// Unlike `as_float`, integers are accepted and converted with `as`, so
+    /// integers beyond 2^53 in magnitude may lose precision.
+    ///
+    /// ```
+    /// use toml::Value;
+    ///
+    /// assert_eq!(Value::Integer(2).as_number_f64(), Some(2.0));
+    /// assert_eq!(Value::Float(2.5).as_number_f64(), Some(2.5));
+    /// assert_eq!(Value::String("2".to_string()).as_number_f64(), None);
+    /// ```
+    pub fn as_number_f64(&self) -> Option<f64> {
+        match *self {
+            Value::Integer(i) => Some(i as f64),
+            Value::Float(f) => Some(f),
+            _ => None,
+        }
+    }
+
     /// Tests whether this value is a float.
     pub fn is_float(&self) -> bool {
         self.as_float().is_some()
@@ -149,6 +487,16 @@ impl Value {
         self.as_str().is_some()
     }
 
+    /// Consumes this value, returning the string if it is a string.
+    ///
+    /// The value is dropped if it is of any other type.
+    pub fn into_string(self) -> Option<String> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
     /// Extracts the datetime value if it is a datetime.
     ///
     /// Note that a parsed TOML value will only contain ISO 8601 dates. An
@@ -190,6 +538,16 @@ impl Value {
         self.as_array().is_some()
     }
 
+    /// Consumes this value, returning the array if it is an array.
+    ///
+    /// The value is dropped if it is of any other type.
+    pub fn into_array(self) -> Option<Array> {
+        match self {
+            Value::Array(a) => Some(a),
+            _ => None,
+        }
+    }
+
     /// Extracts the table value if it is a table.
     pub fn as_table(&self) -> Option<&Table> {
         match *self {
@@ -211,7 +569,21 @@ impl Value {
         self.as_table().is_some()
     }
 
+    /// Consumes this value, returning the table if it is a table.
+    ///
+    /// The value is dropped if it is of any other type.
+    pub fn into_table(self) -> Option<Table> {
+        match self {
+            Value::Table(t) => Some(t),
+            _ => None,
+        }
+    }
+
     /// Tests whether this and another value have the same type.
+    ///
+    /// Only the variants are compared, so any two arrays or any two tables
+    /// have the same type regardless of what they contain. Elements of a TOML
+    /// array must all have the same type in this sense.
     pub fn same_type(&self, other: &Value) -> bool {
         match (self, other) {
             (&Value::String(..), &Value::String(..))
@@ -222,24 +594,862 @@ impl Value {
             | (&Value::Array(..), &Value::Array(..))
             | (&Value::Table(..), &Value::Table(..)) => true,
 
-            _ => false,
+            _ => false,
+        }
+    }
+
+    /// Tests whether this and another value are equal, treating an integer
+    /// and a float as equal when they hold exactly the same number.
+    ///
+    /// `Integer(3)` is loosely equal to `Float(3.0)`, but not to `Float(3.5)`,
+    /// and an integer too large to be represented exactly as a float is never
+    /// equal to one. Arrays are compared element by element and tables key by
+    /// key with the same rule. Every other value is compared with `==`, so
+    /// strings are never equal to numbers and `NaN` is not equal to itself.
+    ///
+    /// ```
+    /// use toml::Value;
+    ///
+    /// let a: Value = "x = [1, 2]\ny = { z = 3 }".parse().unwrap();
+    /// let b: Value = "x = [1.0, 2.0]\ny = { z = 3.0 }".parse().unwrap();
+    /// assert!(a != b);
+    /// assert!(a.loosely_eq(&b));
+    /// ```
+    pub fn loosely_eq(&self, other: &Value) -> bool {
+        match (self, other) {
+            (&Value::Integer(i), &Value::Float(f)) | (&Value::Float(f), &Value::Integer(i)) => {
+                f.fract() == 0.0
+                    && f >= -9_223_372_036_854_775_808.0
+                    && f < 9_223_372_036_854_775_808.0
+                    && f as i64 == i
+            }
+            (&Value::Array(ref a), &Value::Array(ref b)) => {
+                a.len() == b.len() && a.iter().zip(b).all(|(a, b)| a.loosely_eq(b))
+            }
+            (&Value::Table(ref a), &Value::Table(ref b)) => {
+                a.len() == b.len()
+                    && a.iter()
+                        .all(|(k, v)| b.get(k).map_or(false, |other| v.loosely_eq(other)))
+            }
+            (a, b) => a == b,
+        }
+    }
+
+    /// Returns a human-readable representation of the type of this value.
+    pub fn type_str(&self) -> &'static str {
+        match *self {
+            Value::String(..) => "string",
+            Value::Integer(..) => "integer",
+            Value::Float(..) => "float",
+            Value::Boolean(..) => "boolean",
+            Value::Datetime(..) => "datetime",
+            Value::Array(..) => "array",
+            Value::Table(..) => "table",
+        }
+    }
+
+    /// Returns how deeply tables and arrays are nested in this value.
+    ///
+    /// A scalar has a depth of 0, and a table or an array is one deeper than
+    /// the deepest value it holds, so an empty table or array has a depth
+    /// of 1. Tables and arrays are counted alike, whether they were written
+    /// with headers, dotted keys or inline.
+    ///
+    /// ```
+    /// use toml::Value;
+    ///
+    /// let value: Value = "a = 1\n[b]\nc = [[1], [2]]".parse().unwrap();
+    /// assert_eq!(value["a"].depth(), 0);
+    /// assert_eq!(value["b"]["c"].depth(), 2);
+    /// assert_eq!(value.depth(), 4);
+    /// ```
+    pub fn depth(&self) -> usize {
+        // Walks the value with an explicit stack so that measuring a
+        // pathological value can't overflow the stack itself.
+        let mut max = 0;
+        let mut stack = vec![(self, 0)];
+        while let Some((value, depth)) = stack.pop() {
+            let depth = depth + 1;
+            match *value {
+                Value::Array(ref array) => stack.extend(array.iter().map(|v| (v, depth))),
+                Value::Table(ref table) => stack.extend(table.values().map(|v| (v, depth))),
+                _ => continue,
+            }
+            max = cmp::max(max, depth);
+        }
+        max
+    }
+
+    /// Deeply merges `other` into this value.
+    ///
+    /// If both values are tables, each key of `other` is merged into the
+    /// corresponding entry of `self`, recursing into nested tables and adding
+    /// keys which are not yet present. In every other case `other` replaces
+    /// `self`, so on conflicts between scalars the value from `other` wins.
+    ///
+    /// Note that arrays are not merged element by element or concatenated,
+    /// an array in `other` replaces whatever was in `self` wholesale.
+    pub fn merge(&mut self, other: Value) {
+        match (self, other) {
+            (&mut Value::Table(ref mut table), Value::Table(other)) => {
+                for (key, value) in other {
+                    match table.entry(key) {
+                        Entry::Occupied(mut entry) => entry.get_mut().merge(value),
+                        Entry::Vacant(entry) => {
+                            entry.insert(value);
+                        }
+                    }
+                }
+            }
+            (this, other) => *this = other,
+        }
+    }
+
+    /// Merges several documents into one, reporting which two documents
+    /// collide when they define the same key.
+    ///
+    /// Each document comes with a label naming where it came from, such as
+    /// the path of the file it was read from. Tables are merged the same way
+    /// as by `merge`, but unlike `merge` a key which is given a value by two
+    /// documents is an error rather than being overwritten, unless both
+    /// values are tables. This applies to arrays and to values which happen
+    /// to be equal too. Merging no documents gives an empty table.
+    ///
+    /// ```
+    /// use toml::Value;
+    ///
+    /// let base: Value = "[server]\nport = 80".parse().unwrap();
+    /// let local: Value = "[server]\nhost = 'a'".parse().unwrap();
+    /// let docs = vec![("base.toml".to_string(), base), ("local.toml".to_string(), local)];
+    /// let merged = Value::merge_documents(docs.clone()).unwrap();
+    /// assert_eq!(merged["server"]["host"].as_str(), Some("a"));
+    /// assert_eq!(merged["server"]["port"].as_integer(), Some(80));
+    ///
+    /// let extra: Value = "[server]\nport = 8080".parse().unwrap();
+    /// let mut docs = docs;
+    /// docs.push(("extra.toml".to_string(), extra));
+    /// let err = Value::merge_documents(docs).unwrap_err();
+    /// assert_eq!(
+    ///     err.to_string(),
+    ///     "`server.port` is defined in both `base.toml` and `extra.toml`"
+    /// );
+    /// ```
+    pub fn merge_documents(docs: Vec<(String, Value)>) -> Result<Value, MergeError> {
+        let (labels, docs): (Vec<_>, Vec<_>) = docs.into_iter().unzip();
+        let mut docs = docs.into_iter();
+        let mut merged = match docs.next() {
+            Some(doc) => doc,
+            None => return Ok(Value::Table(Table::new())),
+        };
+        // The document which first defined each value, keyed by its path.
+        // Values inside one which is listed belong to the same document.
+        let mut owners = BTreeMap::new();
+        owners.insert(Vec::new(), 0);
+        for (source, doc) in (1..).zip(docs) {
+            let mut path = Vec::new();
+            merge_document(&mut merged, doc, &mut path, source, &mut owners).map_err(|first| {
+                MergeError {
+                    path: path.join("."),
+                    first: labels[first].clone(),
+                    second: labels[source].clone(),
+                }
+            })?;
+        }
+        Ok(merged)
+    }
+
+    /// Returns an iterator over the keys of this table, or `None` if this is
+    /// not a table.
+    ///
+    /// Keys are produced in the order of the underlying map, which is sorted
+    /// unless the `preserve_order` feature is enabled, in which case it is
+    /// the order the keys were inserted in.
+    ///
+    /// ```
+    /// let value: toml::Value = "a = { c = 2 }\nb = 1".parse().unwrap();
+    /// assert_eq!(value.keys().unwrap().collect::<Vec<_>>(), ["a", "b"]);
+    /// assert!(value["b"].keys().is_none());
+    /// ```
+    pub fn keys(&self) -> Option<Keys> {
+        match *self {
+            Value::Table(ref table) => Some(Keys { iter: table.keys() }),
+            _ => None,
+        }
+    }
+
+    /// Returns the keys of this table as owned strings, or `None` if this is
+    /// not a table.
+    ///
+    /// Only the keys are copied, not the values they hold, so this is much
+    /// cheaper than cloning a large table just to hold on to its keys while
+    /// the table itself is modified. When the keys are only read, `keys`
+    /// avoids copying them at all, just as `get`, `lookup` and the `as_*`
+    /// accessors hand out references to values rather than copies.
+    ///
+    /// ```
+    /// let mut value: toml::Value = "a = { c = 2 }\nb = 1".parse().unwrap();
+    /// for key in value.keys_cloned().unwrap() {
+    ///     let old = value.remove(&key).unwrap();
+    ///     value.as_table_mut().unwrap().insert(key.to_uppercase(), old);
+    /// }
+    /// assert_eq!(value.keys_cloned().unwrap(), ["A", "B"]);
+    /// assert!(value["B"].keys_cloned().is_none());
+    /// ```
+    pub fn keys_cloned(&self) -> Option<Vec<String>> {
+        match *self {
+            Value::Table(ref table) => Some(table.keys().cloned().collect()),
+            _ => None,
+        }
+    }
+
+    /// Returns an iterator over every value in this document which is not a
+    /// table or an array, along with its dotted path.
+    ///
+    /// Paths are built the same way `lookup` reads them: table keys are joined
+    /// with `.`, and array elements contribute their index. Leaves are visited
+    /// depth-first in the order of the underlying map. Empty tables and arrays
+    /// have no leaves and so don't show up at all, and if `self` is not a
+    /// container it is the only leaf, with an empty path.
+    ///
+    /// ```
+    /// let value: toml::Value = "
+    ///     name = 'app'
+    ///     [servers.alpha]
+    ///     ports = [80, 443]
+    /// ".parse().unwrap();
+    ///
+    /// let paths = value.iter_leaves().map(|(path, _)| path).collect::<Vec<_>>();
+    /// assert_eq!(paths, ["name", "servers.alpha.ports.0", "servers.alpha.ports.1"]);
+    /// ```
+    pub fn iter_leaves(&self) -> Leaves {
+        Leaves {
+            root: Some(self),
+            stack: Vec::new(),
+        }
+    }
+
+    /// Collects every leaf of this value into a map from its dotted path.
+    ///
+    /// This holds the same pairs as `iter_leaves`, so a leaf inside a table
+    /// is keyed by the keys leading to it joined with `.`, and an element of
+    /// an array is keyed by its index: `servers.alpha.ports.1` is the second
+    /// port of `servers.alpha`. Keys are not quoted, so a key which itself
+    /// contains a `.` can give the same path as a nested leaf, as `"a.b" = 1`
+    /// does next to `a = { b = 2 }`. When paths collide like this only the
+    /// leaf which `iter_leaves` visits last is kept. Empty tables and arrays
+    /// are left out. The map is always sorted by path, so `ports.10` comes
+    /// before `ports.2`.
+    ///
+    /// `Value::unflatten` turns such a map back into a nested value.
+    ///
+    /// ```
+    /// let value: toml::Value = "
+    ///     name = 'app'
+    ///     [servers.alpha]
+    ///     ports = [80, 443]
+    /// ".parse().unwrap();
+    ///
+    /// let flat = value.flatten();
+    /// assert_eq!(flat.len(), 3);
+    /// assert_eq!(flat["name"].as_str(), Some("app"));
+    /// assert_eq!(flat["servers.alpha.ports.1"].as_integer(), Some(443));
+    /// ```
+    pub fn flatten(&self) -> BTreeMap<String, Value> {
+        self.iter_leaves()
+            .map(|(path, value)| (path, value.clone()))
+            .collect()
+    }
+
+    /// Builds a table from a map of dotted paths to values, the reverse of
+    /// `flatten`.
+    ///
+    /// Each value is placed with `insert`, creating the tables along its path,
+    /// and then every table whose keys are exactly `0`, `1`, ... up to its
+    /// length is turned into an array of its values in that order. Since
+    /// `flatten` leaves out empty tables and arrays, and writes a table keyed
+    /// by indices the same way as an array, the round trip only gives back
+    /// the original value when it has neither.
+    ///
+    /// A path which runs through a value that is not a table, such as `a.b`
+    /// when `a` is an integer, is an error naming that value.
+    ///
+    /// ```
+    /// use std::collections::BTreeMap;
+    /// use toml::Value;
+    ///
+    /// let mut flat = BTreeMap::new();
+    /// flat.insert("name".to_string(), Value::from("app"));
+    /// flat.insert("ports.0".to_string(), Value::from(80));
+    /// flat.insert("ports.1".to_string(), Value::from(443));
+    /// let value = Value::unflatten(flat).unwrap();
+    /// assert_eq!(value.to_string(), "name = \"app\"\nports = [80, 443]\n");
+    ///
+    /// let mut flat = BTreeMap::new();
+    /// flat.insert("a".to_string(), Value::from(1));
+    /// flat.insert("a.b".to_string(), Value::from(2));
+    /// let err = Value::unflatten(flat).unwrap_err();
+    /// assert_eq!(err.path(), "a");
+    /// ```
+    pub fn unflatten(map: BTreeMap<String, Value>) -> Result<Value, InsertError> {
+        fn indexed(t: &Table) -> bool {
+            !t.is_empty() && (0..t.len()).all(|i| t.contains_key(&i.to_string()))
+        }
+
+        let mut value = Value::Table(Table::new());
+        for (path, leaf) in map {
+            value.insert(&path, leaf)?;
+        }
+        value.walk_mut(|value| {
+            let len = match *value {
+                Value::Table(ref t) if indexed(t) => t.len(),
+                _ => return,
+            };
+            if let Value::Table(mut t) = mem::replace(value, Value::Array(Vec::new())) {
+                let array = (0..len).map(|i| t.remove(&i.to_string()).unwrap());
+                *value = Value::Array(array.collect());
+            }
+        });
+        Ok(value)
+    }
+
+    /// Lists the changes which turn this value into `other`.
+    ///
+    /// Tables are compared key by key, recursing into tables present on both
+    /// sides, and each change is reported with a dotted path like the ones
+    /// accepted by `lookup`. Any other pair of values which differ, including
+    /// arrays, is reported as a single `Change::Modified` holding both sides;
+    /// arrays are never compared element by element. If neither value is a
+    /// table the path of the change is empty.
+    ///
+    /// Changes to keys of a table are listed in the order of `self`, followed
+    /// by the keys which were added.
+    ///
+    /// ```
+    /// use toml::value::{Change, Value};
+    ///
+    /// let old: Value = "name = 'app'\n[server]\nhost = 'a'\nport = 80".parse().unwrap();
+    /// let new: Value = "name = 'app'\n[server]\nport = 8080\ntls = true".parse().unwrap();
+    ///
+    /// assert_eq!(
+    ///     old.diff(&new),
+    ///     [
+    ///         Change::Removed { path: "server.host".to_string(), value: Value::from("a") },
+    ///         Change::Modified {
+    ///             path: "server.port".to_string(),
+    ///             old: Value::from(80),
+    ///             new: Value::from(8080),
+    ///         },
+    ///         Change::Added { path: "server.tls".to_string(), value: Value::from(true) },
+    ///     ]
+    /// );
+    /// ```
+    pub fn diff(&self, other: &Value) -> Vec<Change> {
+        let mut changes = Vec::new();
+        diff(None, self, other, &mut changes);
+        changes
+    }
+
+    /// Normalizes this value so that values which mean the same thing are
+    /// also written out the same way, for example before hashing the
+    /// serialized form.
+    ///
+    /// Tables and arrays are normalized recursively, and only these changes
+    /// are made:
+    ///
+    /// * Offset datetimes are converted to UTC, so `1979-05-27T00:32:00-07:00`
+    ///   becomes `1979-05-27T07:32:00Z`.
+    /// * Negative zero becomes positive zero.
+    /// * Every NaN becomes the same positive NaN.
+    ///
+    /// Integers and floats are different types in TOML, so `3` and `3.0` are
+    /// left as they are. Tables keep their order, which only matters when the
+    /// `preserve_order` feature is enabled.
+    ///
+    /// ```
+    /// let mut a: toml::Value = "t = 1979-05-27T00:32:00-07:00\nf = -0.0".parse().unwrap();
+    /// let mut b: toml::Value = "t = 1979-05-27T07:32:00Z\nf = 0.0".parse().unwrap();
+    /// assert_ne!(a.to_string(), b.to_string());
+    ///
+    /// a.canonicalize();
+    /// b.canonicalize();
+    /// assert_eq!(a.to_string(), b.to_string());
+    /// ```
+    pub fn canonicalize(&mut self) {
+        match *self {
+            Value::Float(ref mut f) => {
+                if f.is_nan() {
+                    *f = f64::NAN.abs();
+                } else if *f == 0.0 {
+                    *f = 0.0;
+                }
+            }
+            Value::Datetime(Datetime::OffsetDatetime(ref mut d)) => {
+                use chrono::Offset;
+                *d = d.with_timezone(&::chrono::Utc.fix());
+            }
+            Value::Array(ref mut array) => {
+                for value in array.iter_mut() {
+                    value.canonicalize();
+                }
+            }
+            Value::Table(ref mut table) => {
+                for (_, value) in table.iter_mut() {
+                    value.canonicalize();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Sorts arrays of scalars in place, for output which doesn't depend on
+    /// the order elements were added in.
+    ///
+    /// Tables and arrays are searched recursively. An array is sorted if all
+    /// of its elements are strings, integers, floats, booleans or datetimes of
+    /// the same type, using the ordering of `Value`; NaN sorts after every
+    /// other float. Arrays of tables, arrays of arrays and arrays mixing
+    /// several types are left in their original order, though the values
+    /// inside them are still searched.
+    ///
+    /// ```
+    /// let mut value: toml::Value = "a = [3, 1, 2]\nb = [{ c = ['y', 'x'] }]".parse().unwrap();
+    /// value.sort_arrays();
+    /// assert_eq!(value.to_string(), "a = [1, 2, 3]\n\n[[b]]\nc = [\"x\", \"y\"]\n");
+    /// ```
+    pub fn sort_arrays(&mut self) {
+        match *self {
+            Value::Array(ref mut array) => {
+                let sortable = match array.first() {
+                    Some(&Value::Array(_)) | Some(&Value::Table(_)) | None => false,
+                    Some(first) => array.iter().all(|value| value.same_type(first)),
+                };
+                if sortable {
+                    array.sort_by(|a, b| match (a, b) {
+                        (&Value::Float(a), &Value::Float(b)) => a
+                            .partial_cmp(&b)
+                            .unwrap_or_else(|| a.is_nan().cmp(&b.is_nan())),
+                        _ => a.partial_cmp(b).unwrap_or(cmp::Ordering::Equal),
+                    });
+                } else {
+                    for value in array.iter_mut() {
+                        value.sort_arrays();
+                    }
+                }
+            }
+            Value::Table(ref mut table) => {
+                for (_, value) in table.iter_mut() {
+                    value.sort_arrays();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Calls `f` with every value in this value, including `self` and the
+    /// tables and arrays themselves.
+    ///
+    /// Values are visited depth-first, and a table or array is passed to `f`
+    /// before the values inside it. So if `f` changes a table or array, for
+    /// example by renaming the keys of a table, the walk continues with its
+    /// new contents. The elements of an array are visited in order, and the
+    /// entries of a table in the order its keys are iterated in.
+    ///
+    /// ```
+    /// use toml::Value;
+    ///
+    /// let mut value: Value = "Name = ' app '\n[Server]\nhosts = [' a', 'b ']".parse().unwrap();
+    /// value.walk_mut(|value| match *value {
+    ///     Value::String(ref mut s) => *s = s.trim().to_string(),
+    ///     Value::Table(ref mut t) => {
+    ///         *t = t.iter().map(|(k, v)| (k.to_lowercase(), v.clone())).collect();
+    ///     }
+    ///     _ => {}
+    /// });
+    /// assert_eq!(value.to_string(), "name = \"app\"\n\n[server]\nhosts = [\"a\", \"b\"]\n");
+    /// ```
+    pub fn walk_mut<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&mut Value),
+    {
+        self.walk_mut_with(&mut f)
+    }
+
+    fn walk_mut_with<F>(&mut self, f: &mut F)
+    where
+        F: FnMut(&mut Value),
+    {
+        f(self);
+        match *self {
+            Value::Array(ref mut array) => {
+                for value in array.iter_mut() {
+                    value.walk_mut_with(f);
+                }
+            }
+            Value::Table(ref mut table) => {
+                for (_, value) in table.iter_mut() {
+                    value.walk_mut_with(f);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Replaces scalar values whose key matches `predicate` with the string
+    /// `"***"`, so that secrets can be left out of logs.
+    ///
+    /// Tables and arrays are searched recursively and are never replaced
+    /// themselves, only the scalars inside them. For each scalar `predicate`
+    /// is called with its dotted path, as read by `lookup`, and if that does
+    /// not match then with the last segment of the path on its own. Elements
+    /// of an array are named by their index.
+    ///
+    /// ```
+    /// let mut value: toml::Value = "
+    ///     user = 'admin'
+    ///     [db]
+    ///     password = 'hunter2'
+    ///     replicas = [{ password = 'x', host = 'a' }]
+    /// ".parse().unwrap();
+    ///
+    /// value.redact(|key| key.contains("password"));
+    /// assert_eq!(value["user"].as_str(), Some("admin"));
+    /// assert_eq!(value["db"]["password"].as_str(), Some("***"));
+    /// assert_eq!(value["db"]["replicas"][0]["password"].as_str(), Some("***"));
+    /// assert_eq!(value["db"]["replicas"][0]["host"].as_str(), Some("a"));
+    /// ```
+    pub fn redact<F>(&mut self, predicate: F)
+    where
+        F: Fn(&str) -> bool,
+    {
+        redact(None, self, &predicate);
+    }
+}
+
+/// A single difference between two values, as returned by `Value::diff`.
+#[derive(PartialEq, Clone, Debug)]
+pub enum Change {
+    /// A key was only present in the new value.
+    Added {
+        /// Dotted path of the key
+        path: String,
+        /// The value which was added
+        value: Value,
+    },
+    /// A key was only present in the old value.
+    Removed {
+        /// Dotted path of the key
+        path: String,
+        /// The value which was removed
+        value: Value,
+    },
+    /// A value was replaced by a different one.
+    Modified {
+        /// Dotted path of the value
+        path: String,
+        /// The value before the change
+        old: Value,
+        /// The value after the change
+        new: Value,
+    },
+}
+
+fn diff(path: Option<&str>, old: &Value, new: &Value, changes: &mut Vec<Change>) {
+    let child = |key: &str| match path {
+        Some(path) => format!("{}.{}", path, key),
+        None => key.to_string(),
+    };
+    match (old, new) {
+        (&Value::Table(ref old), &Value::Table(ref new)) => {
+            for (key, value) in old.iter() {
+                match new.get(key) {
+                    Some(other) => diff(Some(&child(key)), value, other, changes),
+                    None => changes.push(Change::Removed {
+                        path: child(key),
+                        value: value.clone(),
+                    }),
+                }
+            }
+            for (key, value) in new.iter() {
+                if !old.contains_key(key) {
+                    changes.push(Change::Added {
+                        path: child(key),
+                        value: value.clone(),
+                    });
+                }
+            }
+        }
+        _ => {
+            if old != new {
+                changes.push(Change::Modified {
+                    path: path.unwrap_or("").to_string(),
+                    old: old.clone(),
+                    new: new.clone(),
+                });
+            }
+        }
+    }
+}
+
+/// Parses an array index in a JSON Pointer, which may not have a sign or
+/// leading zeros.
+fn pointer_index(token: &str) -> Option<usize> {
+    if token.starts_with('+') || (token.starts_with('0') && token.len() > 1) {
+        return None;
+    }
+    token.parse().ok()
+}
+
+fn redact<F>(path: Option<&str>, value: &mut Value, predicate: &F)
+where
+    F: Fn(&str) -> bool,
+{
+    let child = |key: &str| match path {
+        Some(path) => format!("{}.{}", path, key),
+        None => key.to_string(),
+    };
+    match *value {
+        Value::Table(ref mut table) => {
+            for (key, value) in table.iter_mut() {
+                redact(Some(&child(key)), value, predicate);
+            }
+        }
+        Value::Array(ref mut array) => {
+            for (i, value) in array.iter_mut().enumerate() {
+                redact(Some(&child(&i.to_string())), value, predicate);
+            }
+        }
+        _ => {
+            let path = match path {
+                Some(path) => path,
+                None => return,
+            };
+            let key = path.rsplit('.').next().unwrap_or(path);
+            if predicate(path) || predicate(key) {
+                *value = Value::String("***".to_string());
+            }
+        }
+    }
+}
+
+// Merges `doc` into `dst` for `Value::merge_documents`. On a conflict, `path`
+// is left pointing at the key and the index of the other document is returned.
+fn merge_document(
+    dst: &mut Value,
+    doc: Value,
+    path: &mut Vec<String>,
+    source: usize,
+    owners: &mut BTreeMap<Vec<String>, usize>,
+) -> Result<(), usize> {
+    match (dst, doc) {
+        (&mut Value::Table(ref mut table), Value::Table(doc)) => {
+            for (key, value) in doc {
+                path.push(key.clone());
+                match table.entry(key) {
+                    Entry::Occupied(mut entry) => {
+                        merge_document(entry.get_mut(), value, path, source, owners)?
+                    }
+                    Entry::Vacant(entry) => {
+                        entry.insert(value);
+                        owners.insert(path.clone(), source);
+                    }
+                }
+                path.pop();
+            }
+            Ok(())
+        }
+        _ => Err((0..path.len() + 1)
+            .rev()
+            .filter_map(|len| owners.get(&path[..len]))
+            .next()
+            .cloned()
+            .unwrap_or(0)),
+    }
+}
+
+/// The error returned by `Value::merge_documents` when two documents define
+/// the same key.
+#[derive(PartialEq, Clone, Debug)]
+pub struct MergeError {
+    path: String,
+    first: String,
+    second: String,
+}
+
+impl MergeError {
+    /// Dotted path of the key both documents define, empty if the documents
+    /// themselves could not be merged because one is not a table.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Labels of the document which defined the key first and of the one
+    /// which defined it again.
+    pub fn sources(&self) -> (&str, &str) {
+        (&self.first, &self.second)
+    }
+}
+
+impl fmt::Display for MergeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.path.is_empty() {
+            write!(
+                f,
+                "documents `{}` and `{}` are not both tables",
+                self.first, self.second
+            )
+        } else {
+            write!(
+                f,
+                "`{}` is defined in both `{}` and `{}`",
+                self.path, self.first, self.second
+            )
+        }
+    }
+}
+
+impl error::Error for MergeError {}
+
+/// The error returned by `Value::insert` and `Value::unflatten` when a value
+/// along the path is not a table.
+#[derive(PartialEq, Clone, Debug)]
+pub struct InsertError {
+    path: String,
+    found: &'static str,
+}
+
+impl InsertError {
+    fn new(path: String, found: &Value) -> InsertError {
+        InsertError {
+            path: path,
+            found: found.type_str(),
+        }
+    }
+
+    /// Dotted path of the value which is not a table, empty if it is the value
+    /// being inserted into.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+}
+
+impl fmt::Display for InsertError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "cannot insert into a value of type {}", self.found)
+        } else {
+            write!(
+                f,
+                "cannot insert into `{}`, which is of type {}",
+                self.path, self.found
+            )
+        }
+    }
+}
+
+impl error::Error for InsertError {}
+
+/// An iterator over the keys of a table.
+///
+/// This is created by `Value::keys`.
+pub struct Keys<'a> {
+    iter: map::Keys<'a>,
+}
+
+impl<'a> Iterator for Keys<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        self.iter.next().map(|key| &key[..])
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<'a> DoubleEndedIterator for Keys<'a> {
+    fn next_back(&mut self) -> Option<&'a str> {
+        self.iter.next_back().map(|key| &key[..])
+    }
+}
+
+impl<'a> ExactSizeIterator for Keys<'a> {
+    fn len(&self) -> usize {
+        self.iter.len()
+    }
+}
+
+/// An iterator over the leaves of a `Value` and their dotted paths.
+///
+/// This is created by `Value::iter_leaves`.
+pub struct Leaves<'a> {
+    root: Option<&'a Value>,
+    stack: Vec<(String, Children<'a>)>,
+}
+
+enum Children<'a> {
+    Table(map::Iter<'a>),
+    Array(iter::Enumerate<slice::Iter<'a, Value>>),
+}
+
+impl<'a> Children<'a> {
+    fn of(value: &'a Value) -> Option<Children<'a>> {
+        match *value {
+            Value::Table(ref table) => Some(Children::Table(table.iter())),
+            Value::Array(ref array) => Some(Children::Array(array.iter().enumerate())),
+            _ => None,
         }
     }
 
-    /// Returns a human-readable representation of the type of this value.
-    pub fn type_str(&self) -> &'static str {
+    fn next(&mut self) -> Option<(String, &'a Value)> {
         match *self {
-            Value::String(..) => "string",
-            Value::Integer(..) => "integer",
-            Value::Float(..) => "float",
-            Value::Boolean(..) => "boolean",
-            Value::Datetime(..) => "datetime",
-            Value::Array(..) => "array",
-            Value::Table(..) => "table",
+            Children::Table(ref mut iter) => iter.next().map(|(k, v)| (k.clone(), v)),
+            Children::Array(ref mut iter) => iter.next().map(|(i, v)| (i.to_string(), v)),
+        }
+    }
+}
+
+impl<'a> Iterator for Leaves<'a> {
+    type Item = (String, &'a Value);
+
+    fn next(&mut self) -> Option<(String, &'a Value)> {
+        if let Some(root) = self.root.take() {
+            match Children::of(root) {
+                Some(children) => self.stack.push((String::new(), children)),
+                None => return Some((String::new(), root)),
+            }
+        }
+
+        loop {
+            let top = self.stack.len() == 1;
+            let next = match self.stack.last_mut() {
+                Some(&mut (ref prefix, ref mut children)) => children.next().map(|(key, value)| {
+                    if top {
+                        (key, value)
+                    } else {
+                        (format!("{}.{}", prefix, key), value)
+                    }
+                }),
+                None => return None,
+            };
+            match next {
+                Some((path, value)) => match Children::of(value) {
+                    Some(children) => self.stack.push((path, children)),
+                    None => return Some((path, value)),
+                },
+                None => {
+                    self.stack.pop();
+                }
+            }
         }
     }
 }
 
+/// Indexes into a table by key or into an array by position.
+///
+/// There is no "null" variant in TOML for a missing entry to resolve to, so
+/// indexing with a key that is not present, or indexing a value of the wrong
+/// type, panics. Use `get` or `lookup` to chain lookups without panicking.
 impl<I> ops::Index<I> for Value
 where
     I: Index,
@@ -251,6 +1461,9 @@ where
     }
 }
 
+/// Mutably indexes into a table by key or into an array by position.
+///
+/// Like `Index`, this panics if the entry does not exist.
 impl<I> ops::IndexMut<I> for Value
 where
     I: Index,
@@ -303,13 +1516,96 @@ macro_rules! impl_into_value {
 impl_into_value!(String: String);
 impl_into_value!(Integer: i64);
 impl_into_value!(Integer: i32);
+impl_into_value!(Integer: i16);
 impl_into_value!(Integer: i8);
 impl_into_value!(Integer: u8);
+impl_into_value!(Integer: u16);
 impl_into_value!(Integer: u32);
 impl_into_value!(Float: f64);
 impl_into_value!(Float: f32);
 impl_into_value!(Boolean: bool);
 impl_into_value!(Datetime: Datetime);
+impl_into_value!(Table: Table);
+
+#[cfg(feature = "serde_json")]
+impl Value {
+    /// Converts a TOML value into a JSON value.
+    ///
+    /// Datetimes become strings in their TOML representation. JSON has no
+    /// way to represent infinite floats or NaN, so converting one of those
+    /// fails with `NumberInvalid` rather than quietly producing `null`.
+    pub fn into_json(self) -> Result<::serde_json::Value, ::ser::Error> {
+        use serde_json::Value as Json;
+
+        Ok(match self {
+            Value::String(s) => Json::String(s),
+            Value::Integer(i) => Json::Number(i.into()),
+            Value::Float(f) => match ::serde_json::Number::from_f64(f) {
+                Some(n) => Json::Number(n),
+                None => return Err(::ser::Error::NumberInvalid),
+            },
+            Value::Boolean(b) => Json::Bool(b),
+            Value::Datetime(d) => Json::String(d.to_string()),
+            Value::Array(a) => Json::Array(
+                a.into_iter()
+                    .map(Value::into_json)
+                    .collect::<Result<_, _>>()?,
+            ),
+            Value::Table(t) => {
+                let mut object = ::serde_json::Map::new();
+                for (k, v) in t {
+                    object.insert(k, v.into_json()?);
+                }
+                Json::Object(object)
+            }
+        })
+    }
+
+    /// Converts a JSON value into a TOML value.
+    ///
+    /// JSON numbers become integers if they fit in an `i64` and floats if they
+    /// have a fractional part or exponent. Integers which don't fit in an
+    /// `i64` can't be represented and fail with `NumberInvalid`, rather than
+    /// being rounded to a float. TOML has no equivalent of `null`, so members
+    /// of an object which are `null` are dropped, while a `null` anywhere else
+    /// is an `UnsupportedNone` error. Arrays whose elements are not all of the
+    /// same type fail with `ArrayMixedType`, since TOML doesn't allow them.
+    pub fn from_json(json: ::serde_json::Value) -> Result<Value, ::ser::Error> {
+        use serde_json::Value as Json;
+
+        Ok(match json {
+            Json::Null => return Err(::ser::Error::UnsupportedNone),
+            Json::Bool(b) => Value::Boolean(b),
+            Json::Number(n) => match (n.as_i64(), n.as_f64()) {
+                (Some(i), _) => Value::Integer(i),
+                (None, Some(f)) if n.is_f64() => Value::Float(f),
+                _ => return Err(::ser::Error::NumberInvalid),
+            },
+            Json::String(s) => Value::String(s),
+            Json::Array(a) => {
+                let values = a
+                    .into_iter()
+                    .map(Value::from_json)
+                    .collect::<Result<Vec<_>, _>>()?;
+                if let Some(first) = values.first() {
+                    if values.iter().any(|v| !v.same_type(first)) {
+                        return Err(::ser::Error::ArrayMixedType);
+                    }
+                }
+                Value::Array(values)
+            }
+            Json::Object(o) => {
+                let mut table = Table::new();
+                for (k, v) in o {
+                    if !v.is_null() {
+                        table.insert(k, Value::from_json(v)?);
+                    }
+                }
+                Value::Table(table)
+            }
+        })
+    }
+}
 
 /// Types that can be used to index a `toml::Value`
 ///
@@ -389,6 +1685,32 @@ where
     }
 }
 
+/// Hashes consistently with `PartialEq`, so values which are equal hash the
+/// same.
+///
+/// Floats are hashed by their bits, except that both zeroes hash alike since
+/// they compare equal. Because a NaN is not equal to anything, including
+/// itself, `Value` can't implement `Eq`, so it can't be used as the key of a
+/// `HashMap` directly. Hashing the value yourself, for example as a cache key
+/// after calling `canonicalize`, works as expected.
+impl Hash for Value {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        mem::discriminant(self).hash(state);
+        match *self {
+            Value::String(ref s) => s.hash(state),
+            Value::Integer(i) => i.hash(state),
+            Value::Float(f) => {
+                let f = if f == 0.0 { 0.0 } else { f };
+                f.to_bits().hash(state)
+            }
+            Value::Boolean(b) => b.hash(state),
+            Value::Datetime(ref d) => d.hash(state),
+            Value::Array(ref a) => a.hash(state),
+            Value::Table(ref t) => t.hash(state),
+        }
+    }
+}
+
 impl fmt::Display for Value {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         ::ser::to_string(self)
@@ -535,37 +1857,413 @@ impl<'de> de::Deserialize<'de> for Value {
                 }
                 let mut map = Map::new();
                 map.insert(key, visitor.next_value()?);
-                while let Some(key) = visitor.next_key()? {
-                    if map.contains_key(&key) {
-                        let msg = format!("duplicate key: `{}`", key);
-                        return Err(de::Error::custom(msg));
+                while let Some(key) = visitor.next_key::<String>()? {
+                    // Look the key up once rather than once to check for it
+                    // and again to insert it, which adds up in large tables.
+                    match map.entry(key) {
+                        Entry::Vacant(entry) => {
+                            entry.insert(visitor.next_value()?);
+                        }
+                        Entry::Occupied(entry) => {
+                            let msg = format!("duplicate key: `{}`", entry.key());
+                            return Err(de::Error::custom(msg));
+                        }
                     }
-                    map.insert(key, visitor.next_value()?);
                 }
                 Ok(Value::Table(map))
             }
         }
 
-        deserializer.deserialize_any(ValueVisitor)
+        deserializer.deserialize_any(ValueVisitor)
+    }
+}
+
+/// How deeply the value being deserialized is nested within the outermost
+/// one, along with the limit past which deserialization gives up.
+#[derive(Clone, Copy)]
+struct Depth {
+    current: usize,
+    max: usize,
+}
+
+impl Depth {
+    fn new(max: usize) -> Depth {
+        Depth {
+            current: 0,
+            max: max,
+        }
+    }
+
+    /// Called on reaching an array or table, returns the depth of its
+    /// elements.
+    fn enter(self) -> Result<Depth, ::de::Error> {
+        if self.current > self.max {
+            return Err(::de::Error::from_kind(ErrorKind::MaxDepthExceeded(
+                self.max,
+            )));
+        }
+        Ok(Depth {
+            current: self.current + 1,
+            max: self.max,
+        })
+    }
+}
+
+// `Value` and `&Value` start counting from the top with the default limit, and
+// leave the rest to a deserializer which tracks the depth.
+macro_rules! forward_to_value_deserializer {
+    ($de:ident: $($method:ident($($arg:ident: $ty:ty),*))*) => {
+        $(
+            fn $method<V>(self, $($arg: $ty,)* visitor: V) -> Result<V::Value, ::de::Error>
+            where
+                V: de::Visitor<'de>,
+            {
+                let de = $de {
+                    value: self,
+                    depth: Depth::new(::de::DEFAULT_MAX_DEPTH),
+                };
+                de::Deserializer::$method(de, $($arg,)* visitor)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for Value {
+    type Error = ::de::Error;
+
+    forward_to_value_deserializer! {
+        ValueDeserializer:
+        deserialize_any()
+        deserialize_enum(name: &'static str, variants: &'static [&'static str])
+        deserialize_option()
+        deserialize_newtype_struct(name: &'static str)
+        deserialize_unit_struct(name: &'static str)
+        deserialize_tuple(len: usize)
+        deserialize_tuple_struct(name: &'static str, len: usize)
+        deserialize_ignored_any()
+    }
+
+    forward_to_deserialize_any! {
+        bool u8 u16 u32 u64 i8 i16 i32 i64 f32 f64 char str string unit seq
+        bytes byte_buf map struct identifier
+    }
+}
+
+/// Deserializes from an owned `Value`, keeping track of how deeply arrays and
+/// tables are nested.
+struct ValueDeserializer {
+    value: Value,
+    depth: Depth,
+}
+
+impl<'de> de::Deserializer<'de> for ValueDeserializer {
+    type Error = ::de::Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, ::de::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        match self.value {
+            Value::Boolean(v) => visitor.visit_bool(v),
+            Value::Integer(n) => visitor.visit_i64(n),
+            Value::Float(n) => visitor.visit_f64(n),
+            Value::String(v) => visitor.visit_string(v),
+            Value::Datetime(v) => visitor.visit_string(v.to_string()),
+            Value::Array(v) => {
+                let len = v.len();
+                let mut deserializer = SeqDeserializer::new(v, self.depth.enter()?);
+                let seq = visitor.visit_seq(&mut deserializer)?;
+                let remaining = deserializer.iter.len();
+                if remaining == 0 {
+                    Ok(seq)
+                } else {
+                    Err(de::Error::invalid_length(len, &"fewer elements in array"))
+                }
+            }
+            Value::Table(v) => {
+                let len = v.len();
+                let mut deserializer = MapDeserializer::new(v, self.depth.enter()?);
+                let map = visitor.visit_map(&mut deserializer)?;
+                let remaining = deserializer.iter.len();
+                if remaining == 0 {
+                    Ok(map)
+                } else {
+                    Err(de::Error::invalid_length(len, &"fewer elements in map"))
+                }
+            }
+        }
+    }
+
+    #[inline]
+    fn deserialize_enum<V>(
+        self,
+        _name: &str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, ::de::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        match self.value {
+            Value::String(variant) => visitor.visit_enum(variant.into_deserializer()),
+            Value::Table(table) => {
+                if table.len() != 1 {
+                    return Err(::de::Error::from_kind(ErrorKind::Wanted {
+                        expected: "exactly 1 element",
+                        found: if table.is_empty() {
+                            "zero elements"
+                        } else {
+                            "more than 1 element"
+                        },
+                    }));
+                }
+                let (variant, value) = table.into_iter().next().unwrap();
+                visitor.visit_enum(EnumDeserializer {
+                    variant: variant,
+                    value: value,
+                    depth: self.depth.enter()?,
+                })
+            }
+            _ => Err(de::Error::invalid_type(
+                de::Unexpected::UnitVariant,
+                &"string or table",
+            )),
+        }
+    }
+
+    // `None` is interpreted as a missing field so be sure to implement `Some`
+    // as a present field.
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, ::de::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, ::de::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    // TOML has no unit type, so unit structs are read from empty tables.
+    fn deserialize_unit_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, ::de::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        match self.value {
+            Value::Table(ref table) if table.is_empty() => visitor.visit_unit(),
+            Value::Table(_) => Err(::de::Error::from_kind(ErrorKind::ExpectedEmptyTable)),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value, ::de::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        if let Value::Array(ref values) = self.value {
+            if values.len() != len {
+                return Err(::de::Error::from_kind(ErrorKind::ArrayLength {
+                    expected: len,
+                    found: values.len(),
+                }));
+            }
+        }
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_tuple_struct<V>(
+        self,
+        _name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, ::de::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_tuple(len, visitor)
+    }
+
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value, ::de::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+
+    forward_to_deserialize_any! {
+        bool u8 u16 u32 u64 i8 i16 i32 i64 f32 f64 char str string unit seq
+        bytes byte_buf map struct identifier
+    }
+}
+
+struct SeqDeserializer {
+    iter: vec::IntoIter<Value>,
+    index: usize,
+    depth: Depth,
+}
+
+impl SeqDeserializer {
+    fn new(vec: Vec<Value>, depth: Depth) -> Self {
+        SeqDeserializer {
+            iter: vec.into_iter(),
+            index: 0,
+            depth: depth,
+        }
+    }
+}
+
+impl<'de> de::SeqAccess<'de> for SeqDeserializer {
+    type Error = ::de::Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, ::de::Error>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        let value = match self.iter.next() {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+        let index = self.index;
+        self.index += 1;
+        let value = ValueDeserializer {
+            value: value,
+            depth: self.depth,
+        };
+        seed.deserialize(value).map(Some).map_err(|mut error| {
+            error.add_key_context(&index.to_string());
+            error
+        })
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        match self.iter.size_hint() {
+            (lower, Some(upper)) if lower == upper => Some(upper),
+            _ => None,
+        }
+    }
+}
+
+struct MapDeserializer {
+    iter: <Map<String, Value> as IntoIterator>::IntoIter,
+    value: Option<(String, Value)>,
+    depth: Depth,
+}
+
+impl MapDeserializer {
+    fn new(map: Map<String, Value>, depth: Depth) -> Self {
+        MapDeserializer {
+            iter: map.into_iter(),
+            value: None,
+            depth: depth,
+        }
+    }
+}
+
+impl<'de> de::MapAccess<'de> for MapDeserializer {
+    type Error = ::de::Error;
+
+    fn next_key_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, ::de::Error>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        match self.iter.next() {
+            Some((key, value)) => {
+                self.value = Some((key.clone(), value));
+                seed.deserialize(::de::StrDeserializer::new(key.into()))
+                    .map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<T>(&mut self, seed: T) -> Result<T::Value, ::de::Error>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        let (key, res) = match self.value.take() {
+            Some((key, value)) => {
+                let value = ValueDeserializer {
+                    value: value,
+                    depth: self.depth,
+                };
+                (key, seed.deserialize(value))
+            }
+            None => return Err(de::Error::custom("value is missing")),
+        };
+        res.map_err(|mut error| {
+            error.add_key_context(&key);
+            error
+        })
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        match self.iter.size_hint() {
+            (lower, Some(upper)) if lower == upper => Some(upper),
+            _ => None,
+        }
+    }
+}
+
+/// Deserializes from a borrowed `Value`, only cloning data which the type
+/// being deserialized takes ownership of. Strings may be borrowed directly.
+impl<'de> de::Deserializer<'de> for &'de Value {
+    type Error = ::de::Error;
+
+    forward_to_value_deserializer! {
+        RefDeserializer:
+        deserialize_any()
+        deserialize_enum(name: &'static str, variants: &'static [&'static str])
+        deserialize_option()
+        deserialize_newtype_struct(name: &'static str)
+        deserialize_unit_struct(name: &'static str)
+        deserialize_tuple(len: usize)
+        deserialize_tuple_struct(name: &'static str, len: usize)
+        deserialize_ignored_any()
+    }
+
+    forward_to_deserialize_any! {
+        bool u8 u16 u32 u64 i8 i16 i32 i64 f32 f64 char str string unit seq
+        bytes byte_buf map struct identifier
     }
 }
 
-impl<'de> de::Deserializer<'de> for Value {
+/// The borrowed counterpart of `ValueDeserializer`.
+struct RefDeserializer<'de> {
+    value: &'de Value,
+    depth: Depth,
+}
+
+impl<'de> de::Deserializer<'de> for RefDeserializer<'de> {
     type Error = ::de::Error;
 
     fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, ::de::Error>
     where
         V: de::Visitor<'de>,
     {
-        match self {
+        match *self.value {
             Value::Boolean(v) => visitor.visit_bool(v),
             Value::Integer(n) => visitor.visit_i64(n),
             Value::Float(n) => visitor.visit_f64(n),
-            Value::String(v) => visitor.visit_string(v),
-            Value::Datetime(v) => visitor.visit_string(v.to_string()),
-            Value::Array(v) => {
+            Value::String(ref v) => visitor.visit_borrowed_str(v),
+            Value::Datetime(ref v) => visitor.visit_string(v.to_string()),
+            Value::Array(ref v) => {
                 let len = v.len();
-                let mut deserializer = SeqDeserializer::new(v);
+                let mut deserializer = SeqRefDeserializer {
+                    iter: v.iter(),
+                    index: 0,
+                    depth: self.depth.enter()?,
+                };
                 let seq = visitor.visit_seq(&mut deserializer)?;
                 let remaining = deserializer.iter.len();
                 if remaining == 0 {
@@ -574,9 +2272,13 @@ impl<'de> de::Deserializer<'de> for Value {
                     Err(de::Error::invalid_length(len, &"fewer elements in array"))
                 }
             }
-            Value::Table(v) => {
+            Value::Table(ref v) => {
                 let len = v.len();
-                let mut deserializer = MapDeserializer::new(v);
+                let mut deserializer = MapRefDeserializer {
+                    iter: v.iter(),
+                    value: None,
+                    depth: self.depth.enter()?,
+                };
                 let map = visitor.visit_map(&mut deserializer)?;
                 let remaining = deserializer.iter.len();
                 if remaining == 0 {
@@ -598,11 +2300,29 @@ impl<'de> de::Deserializer<'de> for Value {
     where
         V: de::Visitor<'de>,
     {
-        match self {
-            Value::String(variant) => visitor.visit_enum(variant.into_deserializer()),
+        match *self.value {
+            Value::String(ref variant) => visitor.visit_enum(variant.as_str().into_deserializer()),
+            Value::Table(ref table) => {
+                if table.len() != 1 {
+                    return Err(::de::Error::from_kind(ErrorKind::Wanted {
+                        expected: "exactly 1 element",
+                        found: if table.is_empty() {
+                            "zero elements"
+                        } else {
+                            "more than 1 element"
+                        },
+                    }));
+                }
+                let (variant, value) = table.iter().next().unwrap();
+                visitor.visit_enum(EnumRefDeserializer {
+                    variant: variant,
+                    value: value,
+                    depth: self.depth.enter()?,
+                })
+            }
             _ => Err(de::Error::invalid_type(
                 de::Unexpected::UnitVariant,
-                &"string only",
+                &"string or table",
             )),
         }
     }
@@ -627,61 +2347,103 @@ impl<'de> de::Deserializer<'de> for Value {
         visitor.visit_newtype_struct(self)
     }
 
+    // TOML has no unit type, so unit structs are read from empty tables.
+    fn deserialize_unit_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, ::de::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        match *self.value {
+            Value::Table(ref table) if table.is_empty() => visitor.visit_unit(),
+            Value::Table(_) => Err(::de::Error::from_kind(ErrorKind::ExpectedEmptyTable)),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value, ::de::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        if let Value::Array(ref values) = *self.value {
+            if values.len() != len {
+                return Err(::de::Error::from_kind(ErrorKind::ArrayLength {
+                    expected: len,
+                    found: values.len(),
+                }));
+            }
+        }
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_tuple_struct<V>(
+        self,
+        _name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, ::de::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_tuple(len, visitor)
+    }
+
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value, ::de::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+
     forward_to_deserialize_any! {
         bool u8 u16 u32 u64 i8 i16 i32 i64 f32 f64 char str string unit seq
-        bytes byte_buf map unit_struct tuple_struct struct
-        tuple ignored_any identifier
+        bytes byte_buf map struct identifier
     }
 }
 
-struct SeqDeserializer {
-    iter: vec::IntoIter<Value>,
-}
-
-impl SeqDeserializer {
-    fn new(vec: Vec<Value>) -> Self {
-        SeqDeserializer {
-            iter: vec.into_iter(),
-        }
-    }
+struct SeqRefDeserializer<'de> {
+    iter: ::std::slice::Iter<'de, Value>,
+    index: usize,
+    depth: Depth,
 }
 
-impl<'de> de::SeqAccess<'de> for SeqDeserializer {
+impl<'de> de::SeqAccess<'de> for SeqRefDeserializer<'de> {
     type Error = ::de::Error;
 
     fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, ::de::Error>
     where
         T: de::DeserializeSeed<'de>,
     {
-        match self.iter.next() {
-            Some(value) => seed.deserialize(value).map(Some),
-            None => Ok(None),
-        }
+        let value = match self.iter.next() {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+        let index = self.index;
+        self.index += 1;
+        let value = RefDeserializer {
+            value: value,
+            depth: self.depth,
+        };
+        seed.deserialize(value).map(Some).map_err(|mut error| {
+            error.add_key_context(&index.to_string());
+            error
+        })
     }
 
     fn size_hint(&self) -> Option<usize> {
-        match self.iter.size_hint() {
-            (lower, Some(upper)) if lower == upper => Some(upper),
-            _ => None,
-        }
+        Some(self.iter.len())
     }
 }
 
-struct MapDeserializer {
-    iter: <Map<String, Value> as IntoIterator>::IntoIter,
-    value: Option<(String, Value)>,
-}
-
-impl MapDeserializer {
-    fn new(map: Map<String, Value>) -> Self {
-        MapDeserializer {
-            iter: map.into_iter(),
-            value: None,
-        }
-    }
+struct MapRefDeserializer<'de> {
+    iter: ::map::Iter<'de>,
+    value: Option<(&'de String, &'de Value)>,
+    depth: Depth,
 }
 
-impl<'de> de::MapAccess<'de> for MapDeserializer {
+impl<'de> de::MapAccess<'de> for MapRefDeserializer<'de> {
     type Error = ::de::Error;
 
     fn next_key_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, ::de::Error>
@@ -690,8 +2452,9 @@ impl<'de> de::MapAccess<'de> for MapDeserializer {
     {
         match self.iter.next() {
             Some((key, value)) => {
-                self.value = Some((key.clone(), value));
-                seed.deserialize(Value::String(key)).map(Some)
+                self.value = Some((key, value));
+                seed.deserialize(::de::StrDeserializer::new(key.as_str().into()))
+                    .map(Some)
             }
             None => Ok(None),
         }
@@ -702,19 +2465,241 @@ impl<'de> de::MapAccess<'de> for MapDeserializer {
         T: de::DeserializeSeed<'de>,
     {
         let (key, res) = match self.value.take() {
-            Some((key, value)) => (key, seed.deserialize(value)),
+            Some((key, value)) => {
+                let value = RefDeserializer {
+                    value: value,
+                    depth: self.depth,
+                };
+                (key, seed.deserialize(value))
+            }
             None => return Err(de::Error::custom("value is missing")),
         };
         res.map_err(|mut error| {
-            error.add_key_context(&key);
+            error.add_key_context(key);
             error
         })
     }
 
     fn size_hint(&self) -> Option<usize> {
-        match self.iter.size_hint() {
-            (lower, Some(upper)) if lower == upper => Some(upper),
-            _ => None,
+        Some(self.iter.len())
+    }
+}
+
+struct EnumDeserializer {
+    variant: String,
+    value: Value,
+    depth: Depth,
+}
+
+impl<'de> de::EnumAccess<'de> for EnumDeserializer {
+    type Error = ::de::Error;
+    type Variant = VariantDeserializer;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, VariantDeserializer), ::de::Error>
+    where
+        V: de::DeserializeSeed<'de>,
+    {
+        let variant = seed.deserialize(self.variant.into_deserializer())?;
+        Ok((
+            variant,
+            VariantDeserializer {
+                value: self.value,
+                depth: self.depth,
+            },
+        ))
+    }
+}
+
+struct VariantDeserializer {
+    value: Value,
+    depth: Depth,
+}
+
+impl<'de> de::VariantAccess<'de> for VariantDeserializer {
+    type Error = ::de::Error;
+
+    fn unit_variant(self) -> Result<(), ::de::Error> {
+        match self.value {
+            Value::Table(ref table) if table.is_empty() => Ok(()),
+            Value::Table(_) => Err(::de::Error::from_kind(ErrorKind::ExpectedEmptyTable)),
+            other => Err(::de::Error::from_kind(ErrorKind::Wanted {
+                expected: "table",
+                found: other.type_str(),
+            })),
+        }
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value, ::de::Error>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        seed.deserialize(ValueDeserializer {
+            value: self.value,
+            depth: self.depth,
+        })
+    }
+
+    fn tuple_variant<V>(self, len: usize, visitor: V) -> Result<V::Value, ::de::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        // Tuple variants are arrays when serialized through `Value`, but a
+        // table keyed by index is accepted too, like in documents.
+        let values = match self.value {
+            Value::Array(values) => values,
+            Value::Table(table) => {
+                let mut values = Vec::with_capacity(table.len());
+                for (index, (key, value)) in table.into_iter().enumerate() {
+                    if key.parse::<usize>().ok() != Some(index) {
+                        return Err(::de::Error::from_kind(ErrorKind::ExpectedTupleIndex {
+                            expected: index,
+                            found: key,
+                        }));
+                    }
+                    values.push(value);
+                }
+                values
+            }
+            other => {
+                return Err(::de::Error::from_kind(ErrorKind::Wanted {
+                    expected: "array or table",
+                    found: other.type_str(),
+                }))
+            }
+        };
+        if values.len() != len {
+            return Err(::de::Error::from_kind(ErrorKind::ExpectedTuple(len)));
+        }
+        let value = ValueDeserializer {
+            value: Value::Array(values),
+            depth: self.depth,
+        };
+        de::Deserializer::deserialize_seq(value, visitor)
+    }
+
+    fn struct_variant<V>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, ::de::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        match self.value {
+            table @ Value::Table(_) => {
+                let value = ValueDeserializer {
+                    value: table,
+                    depth: self.depth,
+                };
+                de::Deserializer::deserialize_any(value, visitor)
+            }
+            other => Err(::de::Error::from_kind(ErrorKind::Wanted {
+                expected: "table",
+                found: other.type_str(),
+            })),
+        }
+    }
+}
+
+struct EnumRefDeserializer<'de> {
+    variant: &'de str,
+    value: &'de Value,
+    depth: Depth,
+}
+
+impl<'de> de::EnumAccess<'de> for EnumRefDeserializer<'de> {
+    type Error = ::de::Error;
+    type Variant = VariantRefDeserializer<'de>;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, VariantRefDeserializer<'de>), ::de::Error>
+    where
+        V: de::DeserializeSeed<'de>,
+    {
+        let variant = seed.deserialize(self.variant.into_deserializer())?;
+        Ok((
+            variant,
+            VariantRefDeserializer {
+                value: self.value,
+                depth: self.depth,
+            },
+        ))
+    }
+}
+
+struct VariantRefDeserializer<'de> {
+    value: &'de Value,
+    depth: Depth,
+}
+
+impl<'de> VariantRefDeserializer<'de> {
+    fn deserializer(&self) -> RefDeserializer<'de> {
+        RefDeserializer {
+            value: self.value,
+            depth: self.depth,
+        }
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for VariantRefDeserializer<'de> {
+    type Error = ::de::Error;
+
+    fn unit_variant(self) -> Result<(), ::de::Error> {
+        match *self.value {
+            Value::Table(ref table) if table.is_empty() => Ok(()),
+            Value::Table(_) => Err(::de::Error::from_kind(ErrorKind::ExpectedEmptyTable)),
+            ref other => Err(::de::Error::from_kind(ErrorKind::Wanted {
+                expected: "table",
+                found: other.type_str(),
+            })),
+        }
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value, ::de::Error>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        seed.deserialize(self.deserializer())
+    }
+
+    fn tuple_variant<V>(self, len: usize, visitor: V) -> Result<V::Value, ::de::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        match *self.value {
+            Value::Array(ref values) if values.len() == len => {
+                de::Deserializer::deserialize_seq(self.deserializer(), visitor)
+            }
+            Value::Array(_) => Err(::de::Error::from_kind(ErrorKind::ExpectedTuple(len))),
+            // Tables keyed by index need reshaping into an array, which
+            // can't be done without taking ownership.
+            Value::Table(_) => {
+                let value = VariantDeserializer {
+                    value: self.value.clone(),
+                    depth: self.depth,
+                };
+                de::VariantAccess::tuple_variant(value, len, visitor)
+            },
+            ref other => Err(::de::Error::from_kind(ErrorKind::Wanted {
+                expected: "array or table",
+                found: other.type_str(),
+            })),
+        }
+    }
+
+    fn struct_variant<V>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, ::de::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        match *self.value {
+            Value::Table(_) => de::Deserializer::deserialize_any(self.deserializer(), visitor),
+            ref other => Err(::de::Error::from_kind(ErrorKind::Wanted {
+                expected: "table",
+                found: other.type_str(),
+            })),
         }
     }
 }
@@ -736,10 +2721,10 @@ impl ser::Serializer for Serializer {
     type SerializeSeq = SerializeVec;
     type SerializeTuple = SerializeVec;
     type SerializeTupleStruct = SerializeVec;
-    type SerializeTupleVariant = SerializeVec;
+    type SerializeTupleVariant = SerializeTupleVariant;
     type SerializeMap = SerializeMap;
     type SerializeStruct = SerializeMap;
-    type SerializeStructVariant = ser::Impossible<Value, ::ser::Error>;
+    type SerializeStructVariant = SerializeStructVariant;
 
     fn serialize_bool(self, value: bool) -> Result<Value, ::ser::Error> {
         Ok(Value::Boolean(value))
@@ -809,7 +2794,7 @@ impl ser::Serializer for Serializer {
     }
 
     fn serialize_unit_struct(self, _name: &'static str) -> Result<Value, ::ser::Error> {
-        Err(::ser::Error::UnsupportedType)
+        Ok(Value::Table(Map::new()))
     }
 
     fn serialize_unit_variant(
@@ -836,13 +2821,15 @@ impl ser::Serializer for Serializer {
         self,
         _name: &'static str,
         _variant_index: u32,
-        _variant: &'static str,
-        _value: &T,
+        variant: &'static str,
+        value: &T,
     ) -> Result<Value, ::ser::Error>
     where
         T: ser::Serialize,
     {
-        Err(::ser::Error::UnsupportedType)
+        let mut map = Map::new();
+        map.insert(variant.to_owned(), Value::try_from(value)?);
+        Ok(Value::Table(map))
     }
 
     fn serialize_none(self) -> Result<Value, ::ser::Error> {
@@ -878,10 +2865,13 @@ impl ser::Serializer for Serializer {
         self,
         _name: &'static str,
         _variant_index: u32,
-        _variant: &'static str,
+        variant: &'static str,
         len: usize,
     ) -> Result<Self::SerializeTupleVariant, ::ser::Error> {
-        self.serialize_seq(Some(len))
+        Ok(SerializeTupleVariant {
+            variant: variant,
+            vec: self.serialize_seq(Some(len))?,
+        })
     }
 
     fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, ::ser::Error> {
@@ -903,10 +2893,13 @@ impl ser::Serializer for Serializer {
         self,
         _name: &'static str,
         _variant_index: u32,
-        _variant: &'static str,
-        _len: usize,
+        variant: &'static str,
+        len: usize,
     ) -> Result<Self::SerializeStructVariant, ::ser::Error> {
-        Err(::ser::Error::UnsupportedType)
+        Ok(SerializeStructVariant {
+            variant: variant,
+            map: self.serialize_map(Some(len))?,
+        })
     }
 }
 
@@ -968,22 +2961,6 @@ impl ser::SerializeTupleStruct for SerializeVec {
     }
 }
 
-impl ser::SerializeTupleVariant for SerializeVec {
-    type Ok = Value;
-    type Error = ::ser::Error;
-
-    fn serialize_field<T: ?Sized>(&mut self, value: &T) -> Result<(), ::ser::Error>
-    where
-        T: ser::Serialize,
-    {
-        ser::SerializeSeq::serialize_element(self, value)
-    }
-
-    fn end(self) -> Result<Value, ::ser::Error> {
-        ser::SerializeSeq::end(self)
-    }
-}
-
 impl ser::SerializeMap for SerializeMap {
     type Ok = Value;
     type Error = ::ser::Error;
@@ -994,6 +2971,8 @@ impl ser::SerializeMap for SerializeMap {
     {
         match Value::try_from(key)? {
             Value::String(s) => self.next_key = Some(s),
+            Value::Integer(i) => self.next_key = Some(i.to_string()),
+            Value::Boolean(b) => self.next_key = Some(b.to_string()),
             _ => return Err(::ser::Error::KeyNotString),
         };
         Ok(())
@@ -1041,6 +3020,56 @@ impl ser::SerializeStruct for SerializeMap {
     }
 }
 
+struct SerializeTupleVariant {
+    variant: &'static str,
+    vec: SerializeVec,
+}
+
+impl ser::SerializeTupleVariant for SerializeTupleVariant {
+    type Ok = Value;
+    type Error = ::ser::Error;
+
+    fn serialize_field<T: ?Sized>(&mut self, value: &T) -> Result<(), ::ser::Error>
+    where
+        T: ser::Serialize,
+    {
+        ser::SerializeSeq::serialize_element(&mut self.vec, value)
+    }
+
+    fn end(self) -> Result<Value, ::ser::Error> {
+        let mut map = Map::new();
+        map.insert(self.variant.to_owned(), ser::SerializeSeq::end(self.vec)?);
+        Ok(Value::Table(map))
+    }
+}
+
+struct SerializeStructVariant {
+    variant: &'static str,
+    map: SerializeMap,
+}
+
+impl ser::SerializeStructVariant for SerializeStructVariant {
+    type Ok = Value;
+    type Error = ::ser::Error;
+
+    fn serialize_field<T: ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), ::ser::Error>
+    where
+        T: ser::Serialize,
+    {
+        ser::SerializeStruct::serialize_field(&mut self.map, key, value)
+    }
+
+    fn end(self) -> Result<Value, ::ser::Error> {
+        let mut map = Map::new();
+        map.insert(self.variant.to_owned(), ser::SerializeStruct::end(self.map)?);
+        Ok(Value::Table(map))
+    }
+}
+
 struct DatetimeOrTable<'a> {
     key: &'a mut String,
 }
@@ -61,4 +61,43 @@ fn bad_times() {
     bad("1997-09-09T30:09:09.09Z");
     bad("1997-09-09T12:69:09.09Z");
     bad("1997-09-09T12:09:69.09Z");
+    bad("1997-13-09");
+    bad("1997-09-32");
+    bad("1997-02-30T09:09:09");
+}
+
+#[test]
+fn components() {
+    use toml::value::Datetime;
+
+    let dt: Datetime = "1997-09-09T09:09:09.090-09:09".parse().unwrap();
+    assert_eq!(dt.date().unwrap().to_string(), "1997-09-09");
+    assert_eq!(dt.time().unwrap().to_string(), "09:09:09.090");
+    assert_eq!(dt.offset().unwrap().local_minus_utc(), -(9 * 3600 + 9 * 60));
+
+    let dt: Datetime = "1997-09-09".parse().unwrap();
+    assert!(dt.date().is_some());
+    assert!(dt.time().is_none());
+    assert!(dt.offset().is_none());
+
+    let dt: Datetime = "09:09:09".parse().unwrap();
+    assert!(dt.date().is_none());
+    assert!(dt.time().is_some());
+    assert!(dt.offset().is_none());
+}
+
+#[test]
+fn kinds() {
+    use toml::value::{Datetime, DatetimeKind};
+
+    let kind = |s: &str| s.parse::<Datetime>().unwrap().kind();
+    assert_eq!(kind("1979-05-27T07:32:00Z"), DatetimeKind::OffsetDatetime);
+    assert_eq!(kind("1979-05-27T00:32:00.999999-07:00"), DatetimeKind::OffsetDatetime);
+    assert_eq!(kind("1979-05-27T07:32:00"), DatetimeKind::LocalDatetime);
+    assert_eq!(kind("1979-05-27"), DatetimeKind::LocalDate);
+    assert_eq!(kind("07:32:00"), DatetimeKind::LocalTime);
+
+    let value: Value = "a = 2020-01-01\nb = 2020-01-01T00:00:00Z".parse().unwrap();
+    assert_eq!(value["a"].as_datetime().unwrap().kind(), DatetimeKind::LocalDate);
+    assert_eq!(value["b"].as_datetime().unwrap().kind(), DatetimeKind::OffsetDatetime);
 }
@@ -100,3 +100,26 @@ fn table() {
                "test = [2]\n\
                 test2 = 2\n");
 }
+
+#[test]
+fn quoted_keys() {
+    let table = Table(map! {
+        "bare-key_1" => Integer(1),
+        "weird key" => Integer(2),
+        "ключ" => Integer(3),
+        "a b" => Table(map! { "c.d" => Integer(4) })
+    });
+    let s = table.to_string();
+    assert_eq!(s,
+               "bare-key_1 = 1\n\
+                \"weird key\" = 2\n\
+                \"ключ\" = 3\n\
+                \n\
+                [\"a b\"]\n\
+                \"c.d\" = 4\n");
+    assert_eq!(s.parse::<toml::Value>().unwrap(), table);
+
+    let empty = Table(map! { "" => Integer(1) });
+    assert_eq!(toml::to_string(&empty), Err(toml::ser::Error::KeyEmpty));
+    assert!("\"\" = 1".parse::<toml::Value>().is_err());
+}
@@ -77,3 +77,70 @@ fn float_inf() {
     float_inf_tests!(f32);
     float_inf_tests!(f64);
 }
+
+#[test]
+fn float_round_trip() {
+    #[derive(Serialize, Deserialize)]
+    struct S {
+        f: f64,
+    }
+
+    let values = [
+        0.1 + 0.2,
+        -0.0,
+        1.0,
+        -1.5,
+        123456789.0,
+        9007199254740993.0,
+        1e16,
+        -1.7976931348623157e308,
+        std::f64::MAX,
+        std::f64::MIN_POSITIVE,
+        5e-324,
+        1e-5,
+        9.999999999999999e-6,
+        std::f64::consts::PI,
+    ];
+    for &f in values.iter() {
+        let s = toml::to_string(&S { f: f }).unwrap();
+        assert!(s.len() < 32, "too long: {}", s);
+        let again: S = toml::from_str(&s).unwrap();
+        assert_eq!(again.f.to_bits(), f.to_bits(), "{}", s);
+        let value: Value = toml::from_str(&s).unwrap();
+        assert!(value["f"].is_float(), "{}", s);
+    }
+
+    let f = |f: f64| toml::to_string(&S { f: f }).unwrap();
+    assert_eq!(f(-0.0), "f = -0.0\n");
+    assert_eq!(f(0.0), "f = 0.0\n");
+    assert_eq!(f(3.0), "f = 3.0\n");
+    assert_eq!(f(0.5), "f = 0.5\n");
+    assert_eq!(f(1e300), "f = 1e300\n");
+    assert_eq!(f(-2.5e-7), "f = -2.5e-7\n");
+}
+
+#[test]
+fn non_finite_round_trip() {
+    #[derive(Serialize, Deserialize)]
+    struct S {
+        f: f64,
+        g: f32,
+    }
+
+    let values = [
+        (std::f64::NAN, "nan"),
+        (std::f64::INFINITY, "inf"),
+        (std::f64::NEG_INFINITY, "-inf"),
+    ];
+    for &(f, repr) in values.iter() {
+        let s = toml::to_string(&S { f: f, g: f as f32 }).unwrap();
+        assert_eq!(s, format!("f = {}\ng = {}\n", repr, repr));
+        let again: S = toml::from_str(&s).unwrap();
+        assert_eq!(again.f.is_nan(), f.is_nan());
+        assert_eq!(again.g.is_nan(), f.is_nan());
+        if !f.is_nan() {
+            assert_eq!(again.f, f);
+            assert_eq!(again.g, f as f32);
+        }
+    }
+}
@@ -363,3 +363,21 @@ fn test_dotted_keys() {
 
     assert_eq!(actual, expected);
 }
+
+#[test]
+fn test_raw_string() {
+    let actual = toml! {
+        path = r"C:\Users\nodejs\templates"
+        [server]
+        regex = r"<\i\c*\s*>"
+    };
+
+    let expected = table! {
+        "path" => "C:\\Users\\nodejs\\templates",
+        "server" => table! {
+            "regex" => "<\\i\\c*\\s*>",
+        },
+    };
+
+    assert_eq!(actual, expected);
+}
@@ -1,3 +1,4 @@
+extern crate serde;
 extern crate toml;
 
 use toml::Value;
@@ -34,6 +35,13 @@ writers. Additionally, great lengths are taken to ensure that the entire\r\n\
 contents are never required to be entirely resident in memory all at once.\r\n\
 \"\"\"\
 ".parse::<Value>().unwrap();
+
+    let table = "\
+basic = \"\"\"\r\none\r\ntwo \\\r\n  three\r\n\"\"\"\r\n\
+literal = '''\r\none\r\ntwo\r\n'''\r\n\
+".parse::<Value>().unwrap();
+    assert_eq!(table["basic"].as_str(), Some("one\ntwo three\n"));
+    assert_eq!(table["literal"].as_str(), Some("one\ntwo\n"));
 }
 
 #[test]
@@ -365,6 +373,49 @@ fn bad_underscores() {
     bad!("foo = 1_0_", "invalid number");
 }
 
+#[test]
+fn integer_prefixes() {
+    let value = "hex = 0xDEAD_beef\noct = 0o755\nbin = 0b1010\nzero = 0x0".parse::<Value>().unwrap();
+    assert_eq!(value["hex"].as_integer(), Some(0xdead_beef));
+    assert_eq!(value["oct"].as_integer(), Some(0o755));
+    assert_eq!(value["bin"].as_integer(), Some(0b1010));
+    assert_eq!(value["zero"].as_integer(), Some(0));
+
+    for &(input, col) in &[
+        ("a = 0x", 6),
+        ("a = 0b", 6),
+        ("a = [0o]", 7),
+        ("a = 0xg1", 6),
+        ("a = 0b102", 6),
+        ("a = 0o8", 6),
+        ("a = 0x_1", 6),
+        ("a = 0X1f", 4),
+    ] {
+        let err = input.parse::<Value>().unwrap_err();
+        assert_eq!(err.to_string(), "invalid number at line 1", "{}", input);
+        assert_eq!(err.line_col(), Some((0, col)), "{}", input);
+    }
+}
+
+#[test]
+fn integer_overflow() {
+    bad!(
+        "foo = 99999999999999999999",
+        "integer `99999999999999999999` is out of range of a 64-bit signed integer at line 1"
+    );
+    bad!("foo = -9_223_372_036_854_775_809", "integer `-9_223_372_036_854_775_809` is out");
+    bad!("foo = +9223372036854775808", "integer `9223372036854775808` is out");
+    bad!("foo = 0x8000_0000_0000_0000", "integer `0x8000_0000_0000_0000` is out");
+    bad!("foo = [1, 0o1777777777777777777777]", "integer `0o1777777777777777777777` is out");
+
+    let err = "a = 1\nfoo = 0xffffffffffffffffff".parse::<Value>().unwrap_err();
+    assert_eq!(err.line_col(), Some((1, 6)));
+
+    let value = "a = 9223372036854775807\nb = -9223372036854775808".parse::<Value>().unwrap();
+    assert_eq!(value["a"].as_integer(), Some(i64::max_value()));
+    assert_eq!(value["b"].as_integer(), Some(i64::min_value()));
+}
+
 #[test]
 fn bad_unicode_codepoint() {
     bad!("foo = \"\\uD800\"", "invalid escape value");
@@ -499,3 +550,392 @@ fn require_newline_after_value() {
 0=0r0=0r=falsefal=false
 "#, "invalid number at line 2");
 }
+
+#[test]
+fn error_line_col() {
+    fn line_col(s: &str) -> (usize, usize) {
+        s.parse::<Value>().unwrap_err().line_col().unwrap()
+    }
+
+    assert_eq!(line_col("a = 1\nb = @"), (1, 4));
+    assert_eq!(line_col("a = 1\r\nb = @"), (1, 4));
+    assert_eq!(line_col("a = \"ééé\" @"), (0, 10));
+    assert_eq!(line_col("a = \"日本\"\nb = \"日本\" @"), (1, 9));
+}
+
+#[test]
+fn recover_all_errors() {
+    let (value, errors) = toml::de::from_str_recover::<Value>(
+        "\
+a = 1
+b = @
+c = [1, 2.0]
+d =
+e = 5
+[bad table]
+f = 6
+[good]
+g = \"unterminated
+h = 'x' 'y'
+i = 9
+",
+    );
+
+    let lines = errors
+        .iter()
+        .map(|e| e.line_col().unwrap().0)
+        .collect::<Vec<_>>();
+    assert_eq!(lines, [1, 2, 3, 5, 8, 9]);
+
+    let value = value.unwrap();
+    assert_eq!(value["a"].as_integer(), Some(1));
+    assert_eq!(value["e"].as_integer(), Some(5));
+    assert_eq!(value.get("f"), None);
+    assert_eq!(value["good"]["i"].as_integer(), Some(9));
+    assert_eq!(value["good"].get("h"), None);
+}
+
+#[test]
+fn recover_deserialize_error() {
+    let (value, errors) = toml::de::from_str_recover::<Value>("[a]\n[a]\n");
+    assert!(value.is_none());
+    assert_eq!(errors.len(), 1);
+
+    let (value, errors) = toml::de::from_str_recover::<Value>("a = 1\n");
+    assert!(value.is_some());
+    assert!(errors.is_empty());
+}
+
+#[test]
+fn recover_unterminated_string_at_line_start() {
+    let (value, errors) = toml::de::from_str_recover::<Value>("a = 1\n\"");
+    assert_eq!(errors.len(), 1);
+    assert_eq!(value.unwrap()["a"].as_integer(), Some(1));
+}
+
+#[test]
+fn dotted_keys() {
+    let value = "server.port = 8080\nserver.host.name = 'a'\n[t]\nx.y = { z.w = 1 }".parse::<Value>().unwrap();
+    assert_eq!(value["server"]["port"].as_integer(), Some(8080));
+    assert_eq!(value["server"]["host"]["name"].as_str(), Some("a"));
+    assert_eq!(value["t"]["x"]["y"]["z"]["w"].as_integer(), Some(1));
+
+    bad!(
+        "server = 1\n\nserver.port = 2",
+        "dotted key attempted to extend non-table `server`, first defined at line 1 at line 3"
+    );
+    bad!(
+        "a.b = 1\na.b.c = 2",
+        "dotted key attempted to extend non-table `b`, first defined at line 1 at line 2"
+    );
+    bad!(
+        "a = { b = [1], b.c = 2 }",
+        "dotted key attempted to extend non-table `b`, first defined at line 1 at line 1"
+    );
+    let err = "server = 1\nserver.port = 2".parse::<Value>().unwrap_err();
+    assert_eq!(err.line_col(), Some((1, 14)));
+}
+
+#[test]
+fn duplicate_key_locations() {
+    bad!("a = 1\nb = 2\na = 3", "duplicate key `a`, first defined at line 1 at line 3");
+    bad!("[t]\nx.y = 1\n\nx.y = 2", "duplicate key `y`, first defined at line 2 at line 4");
+    bad!("a = { b = 1, b = 2 }", "duplicate key `b`, first defined at line 1 at line 1");
+    bad!(
+        "[a]\nb = 1\n[c]\n[a]\n",
+        "redefinition of table `a`, first defined at line 1 for key `a` at line 4"
+    );
+    bad!(
+        "[[a]]\nb = 1\n[a]\n",
+        "redefinition of table `a`, first defined at line 1 for key `a` at line 3"
+    );
+    bad!(
+        "[[a]]\n[a.b]\n[a.b]\n",
+        "redefinition of table `a.b`, first defined at line 2 for key `a.b` at line 3"
+    );
+
+    let (_, errors) = toml::de::from_str_recover::<Value>("a = 1\nb = 2\na = 3\nb = 4\n");
+    let lines = errors
+        .iter()
+        .map(|e| e.line_col().unwrap().0)
+        .collect::<Vec<_>>();
+    assert_eq!(lines, [2, 3]);
+}
+
+#[test]
+fn byte_order_mark() {
+    let value: Value = "\u{feff}a = 1\n[b]\nc = 2".parse().unwrap();
+    assert_eq!(value["a"].as_integer(), Some(1));
+    assert_eq!(value["b"]["c"].as_integer(), Some(2));
+    let value: Value = toml::from_reader(&b"\xef\xbb\xbfa = 1"[..]).unwrap();
+    assert_eq!(value["a"].as_integer(), Some(1));
+
+    // Positions are relative to the input including the byte order mark.
+    let input = "\u{feff}a = 1\nb = @";
+    let err = input.parse::<Value>().unwrap_err();
+    assert_eq!(err.line_col(), Some((1, 4)));
+    let input = "\u{feff}a = @";
+    let err = input.parse::<Value>().unwrap_err();
+    assert_eq!(err.line_col(), Some((0, 5)));
+    assert_eq!(
+        err.render(input),
+        "unexpected character found: `@` at line 1\n  |\n1 | a = @\n  |     ^\n"
+    );
+
+    bad!("a = 1\n\u{feff}b = 2", "unexpected character found");
+}
+
+#[test]
+fn from_reader() {
+    let value: Value = toml::from_reader("a = 1\n[b]\nc = 'x'\n".as_bytes()).unwrap();
+    assert_eq!(value["a"].as_integer(), Some(1));
+    assert_eq!(value["b"]["c"].as_str(), Some("x"));
+
+    let err = toml::from_reader::<_, Value>("a = 1\nb = @".as_bytes()).unwrap_err();
+    assert_eq!(err.line_col(), Some((1, 4)));
+
+    let err = toml::from_reader::<_, Value>(&b"a = 1\nb = \"\xff\""[..]).unwrap_err();
+    assert_eq!(err.line_col(), Some((1, 5)));
+    assert!(err.to_string().contains("invalid utf-8"), "error: {}", err);
+    assert!(err.to_string().contains("at line 2"), "error: {}", err);
+
+    struct Failing;
+    impl std::io::Read for Failing {
+        fn read(&mut self, _: &mut [u8]) -> std::io::Result<usize> {
+            Err(std::io::Error::new(std::io::ErrorKind::Other, "disk on fire"))
+        }
+    }
+    let err = toml::from_reader::<_, Value>(Failing).unwrap_err();
+    assert_eq!(err.to_string(), "disk on fire");
+}
+
+#[test]
+fn from_reader_with_max_len() {
+    let input = "a = 1\n[b]\nc = 'x'\n";
+    let value: Value = toml::from_reader_with_max_len(input.as_bytes(), input.len()).unwrap();
+    assert_eq!(value["b"]["c"].as_str(), Some("x"));
+
+    let err =
+        toml::from_reader_with_max_len::<_, Value>(input.as_bytes(), input.len() - 1).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        format!("input is longer than the maximum of {} bytes", input.len() - 1)
+    );
+    assert_eq!(err.line_col(), None);
+
+    // Only one byte past the limit is ever read.
+    let endless = std::io::repeat(b' ');
+    let err = toml::from_reader_with_max_len::<_, Value>(endless, 1024).unwrap_err();
+    assert!(err.to_string().contains("maximum of 1024 bytes"), "error: {}", err);
+
+    let value: Value = toml::from_reader_with_max_len(&b""[..], 0).unwrap();
+    assert_eq!(value.as_table().map(|t| t.len()), Some(0));
+}
+
+#[test]
+fn render_error() {
+    fn render(s: &str) -> String {
+        s.parse::<Value>().unwrap_err().render(s)
+    }
+
+    assert_eq!(
+        render("a = 1\r\nb = @\r\n"),
+        "unexpected character found: `@` at line 2\n  |\n2 | b = @\n  |     ^\n"
+    );
+    assert_eq!(
+        render("\tb =\t@"),
+        "unexpected character found: `@` at line 1\n  |\n1 |     b =    @\n  |            ^\n"
+    );
+    assert_eq!(
+        render("a = \"日本\" @"),
+        "unexpected character found: `@` at line 1\n  |\n1 | a = \"日本\" @\n  |          ^\n"
+    );
+    assert_eq!(
+        render("\n\n\n\n\n\n\n\n\nx = 1\nx = 2"),
+        "duplicate key `x`, first defined at line 10 at line 11\n   |\n11 | x = 2\n   |     ^\n"
+    );
+
+    let err = "a = 1\nb = @".parse::<Value>().unwrap_err();
+    assert_eq!(err.render(""), err.to_string());
+}
+
+#[test]
+fn mixed_array_types() {
+    bad!("x = [1, 2.0]", "mixed types in an array, expected integer but found float at line 1");
+    bad!("x = ['a', 1979-05-27]", "expected string but found datetime");
+    bad!("x = [1979-05-27, 'a']", "expected datetime but found string");
+    bad!("x = [[1], 1]", "expected array but found integer");
+    bad!("x = [{}, []]", "expected inline table but found array");
+    bad!("x = [[1], ['a']]\ny = [1,\n  2,\n  'three']", "expected integer but found string at line 4");
+
+    let err = "x = [1, 2.0]".parse::<Value>().unwrap_err();
+    assert_eq!(err.line_col(), Some((0, 8)));
+    let err = "x = [1,\n# comment\n\"two\"]".parse::<Value>().unwrap_err();
+    assert_eq!(err.line_col(), Some((2, 0)));
+}
+
+#[test]
+fn max_depth() {
+    fn parse(s: &str, depth: usize) -> Result<Value, toml::de::Error> {
+        let mut d = toml::de::Deserializer::new(s);
+        d.set_max_depth(depth);
+        serde::Deserialize::deserialize(&mut d)
+    }
+
+    let deep = format!("a = {}{}", "[".repeat(200), "]".repeat(200));
+    bad!(&deep, "nested deeper than the maximum of 128 at line 1");
+    let err = deep.parse::<Value>().unwrap_err();
+    assert_eq!(err.line_col(), Some((0, 132)));
+
+    let header = format!("[{}a]", "a.".repeat(200));
+    bad!(&header, "nested deeper than the maximum of 128 at line 1");
+    let inline = format!("a = {}{}", "{b = ".repeat(200), "}".repeat(200));
+    bad!(&inline, "nested deeper than the maximum of 128");
+
+    assert!(parse("[a.b]\nc.d = [[1]]", 5).is_ok());
+    assert!(parse("[a.b]\nc.d = [[1]]", 4).is_err());
+    assert!(parse("[a.b]\nc = {d = {}}", 4).is_ok());
+    assert!(parse("[a.b.c]", 2).is_err());
+    assert!(parse("a = [[1]]\n[b]\nc = [2]", 2).is_ok());
+    assert!(parse("a = 1", 0).is_ok());
+    assert!(parse("a = []", 0).is_err());
+}
+
+#[test]
+fn validate() {
+    assert!(toml::validate("").is_ok());
+    assert!(toml::validate("a = 1\n[b]\nc = [1, 2]\n[[d]]\n[[d]]\ne = { f = 1979-05-27 }\n").is_ok());
+
+    let errors = toml::validate("a = @\nb = [1, 'x']\n[c]\n[c]\n").unwrap_err();
+    let lines = errors
+        .iter()
+        .map(|e| e.line_col().unwrap().0)
+        .collect::<Vec<_>>();
+    assert_eq!(lines, [0, 1, 3]);
+    assert!(errors[2].to_string().contains("redefinition of table `c`"), "error: {}", errors[2]);
+
+    let errors = toml::validate("a = 1\na = 2\n").unwrap_err();
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].to_string().contains("duplicate key `a`"), "error: {}", errors[0]);
+}
+
+#[test]
+fn large_flat_table() {
+    let mut s = String::new();
+    for i in 0..50_000 {
+        s.push_str(&format!("key{} = {}\n", i, i));
+    }
+    let value: Value = s.parse().unwrap();
+    let table = value.as_table().unwrap();
+    assert_eq!(table.len(), 50_000);
+    assert_eq!(table["key49999"].as_integer(), Some(49_999));
+
+    s.push_str("key123 = 0\n");
+    bad!(s, "duplicate key `key123`, first defined at line 124 at line 50001");
+}
+
+#[test]
+fn error_path() {
+    let path = |s: &str| s.parse::<Value>().unwrap_err().path().to_vec();
+
+    assert_eq!(path("a = @"), ["a"]);
+    assert!(path("a = 1 2").is_empty());
+    assert!(path("[a.b\nc = 1").is_empty());
+    assert_eq!(path("[a]\nb.c = @"), ["a", "b", "c"]);
+    assert_eq!(path("[a]\nb = [1, {c = 'x\n'}]"), ["a", "b", "c"]);
+    assert_eq!(
+        path("[servers.alpha]\noptions = { retries = 3, delay = 1.x }"),
+        ["servers", "alpha", "options", "delay"]
+    );
+
+    let err = toml::from_str::<Value>("[a]\nb = 1\n[c]\nd = [1, 'x']").unwrap_err();
+    assert_eq!(err.path(), ["c", "d"]);
+}
+
+#[test]
+fn deserializer_builder() {
+    use serde::Deserialize;
+    use toml::de::DeserializerBuilder;
+
+    let mut builder = DeserializerBuilder::new();
+    builder.max_depth(2);
+    assert!(Value::deserialize(&mut builder.build("a = [[1]]")).is_ok());
+    assert!(Value::deserialize(&mut builder.build("a = [[[1]]]")).is_err());
+
+    let input = "[a] b = 1";
+    assert!(Value::deserialize(&mut DeserializerBuilder::new().build(input)).is_err());
+    let mut de = DeserializerBuilder::new()
+        .require_newline_after_table(false)
+        .build(input);
+    assert_eq!(Value::deserialize(&mut de).unwrap()["a"]["b"].as_integer(), Some(1));
+
+    let input = "[a.b]\n[a]\n[a]\n";
+    assert!(Value::deserialize(&mut DeserializerBuilder::default().build(input)).is_err());
+    let mut de = DeserializerBuilder::new()
+        .allow_duplicate_after_longer_table(true)
+        .build(input);
+    assert!(Value::deserialize(&mut de).is_ok());
+}
+
+#[test]
+fn error_codes() {
+    use toml::de::Code;
+
+    let code = |s: &str| s.parse::<Value>().unwrap_err().code();
+
+    assert_eq!(code("a = @"), Code::UnexpectedToken);
+    assert_eq!(code("a = "), Code::UnexpectedToken);
+    assert_eq!(code("a = 1 2"), Code::UnexpectedToken);
+    assert_eq!(code("a = 'x"), Code::InvalidString);
+    assert_eq!(code("a = \"\\q\""), Code::InvalidString);
+    assert_eq!(code("'''a''' = 1"), Code::InvalidKey);
+    assert_eq!(code("[\"\"]"), Code::InvalidKey);
+    assert_eq!(code("a = 1.x"), Code::InvalidNumber);
+    assert_eq!(code("a = 9223372036854775808"), Code::InvalidNumber);
+    assert_eq!(code("a = 1979-05-27T07:"), Code::InvalidDatetime);
+    assert_eq!(code("a = 1979-02-30"), Code::Other);
+    assert_eq!(code("a = [1, 'x']"), Code::MixedArray);
+    assert_eq!(code("a = 1\na = 2"), Code::DuplicateKey);
+    assert_eq!(code("a = 1\na.b = 2"), Code::DuplicateKey);
+    assert_eq!(code("[a]\n[a]"), Code::InvalidTableHeader);
+    assert_eq!(code("[a]\n[[a]]"), Code::InvalidTableHeader);
+    let deep = format!("a = {}", "[".repeat(200));
+    assert_eq!(code(&deep), Code::LimitExceeded);
+
+    let err = toml::from_str::<u32>("a = 1").unwrap_err();
+    assert_eq!(err.code(), Code::Other);
+}
+
+#[test]
+fn normalize_keys() {
+    use serde::Deserialize;
+    use toml::de::DeserializerBuilder;
+
+    fn nfc(key: &str) -> String {
+        key.replace("e\u{301}", "\u{e9}")
+    }
+
+    let mut builder = DeserializerBuilder::new();
+    builder.normalize_keys(Some(nfc));
+    let parse = |s: &str| Value::deserialize(&mut builder.build(s));
+
+    let value = parse("[\"cafe\u{301}\"]\nx.\"de\u{301}j\u{e0}\" = { \"e\u{301}\" = 1 }").unwrap();
+    let table = &value["caf\u{e9}"]["x"]["d\u{e9}j\u{e0}"];
+    assert_eq!(table["\u{e9}"].as_integer(), Some(1));
+
+    let err = parse("\"caf\u{e9}\" = 1\n\"cafe\u{301}\" = 2").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "duplicate key `caf\u{e9}`, first defined at line 1 at line 2"
+    );
+    let err = parse("[\"caf\u{e9}\"]\n[\"cafe\u{301}\"]").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "redefinition of table `caf\u{e9}`, first defined at line 1 for key `caf\u{e9}` at line 2"
+    );
+
+    let input = "[\"caf\u{e9}\"]\n[\"cafe\u{301}\"]";
+    assert_eq!(input.parse::<Value>().unwrap().as_table().unwrap().len(), 2);
+    let mut de = DeserializerBuilder::new().normalize_keys(None).build(input);
+    assert!(Value::deserialize(&mut de).is_ok());
+}
@@ -306,3 +306,176 @@ fn pretty_tricky_non_literal() {
     println!("\nRESULT:\n{}", result);
     assert_eq!(toml, &result);
 }
+
+const PRETTY_NESTED: &'static str = r##"title = "nested"
+matrix = [[1, 2], [3, 4], []]
+inline = { a = [1, 2, 3], b = { c = "d" } }
+strings = ["a'b", 'c"d', "e\nf", '''
+g''']
+
+[[points]]
+xs = [1.5, 2.5]
+
+[[points]]
+xs = []
+"##;
+
+// Serializing moves tables after other values, which reorders the keys of a
+// table under the `preserve_order` feature, so compare with sorted keys.
+fn sort_keys(value: &toml::Value) -> toml::Value {
+    match *value {
+        toml::Value::Table(ref table) => {
+            let mut entries = table.iter().collect::<Vec<_>>();
+            entries.sort_by(|a, b| a.0.cmp(b.0));
+            toml::Value::Table(
+                entries
+                    .into_iter()
+                    .map(|(key, value)| (key.clone(), sort_keys(value)))
+                    .collect(),
+            )
+        }
+        toml::Value::Array(ref array) => toml::Value::Array(array.iter().map(sort_keys).collect()),
+        ref other => other.clone(),
+    }
+}
+
+#[test]
+fn pretty_round_trip() {
+    let value: toml::Value = toml::from_str(PRETTY_NESTED).unwrap();
+    for &(indent, trailing_comma) in &[(4, true), (2, false), (0, true)] {
+        let mut result = String::new();
+        {
+            let mut serializer = toml::Serializer::pretty(&mut result);
+            serializer.pretty_array_indent(indent);
+            serializer.pretty_array_trailing_comma(trailing_comma);
+            value.serialize(&mut serializer).unwrap();
+        }
+        let again: toml::Value = toml::from_str(&result).unwrap();
+        assert_eq!(sort_keys(&value), sort_keys(&again));
+
+        let mut twice = String::new();
+        {
+            let mut serializer = toml::Serializer::pretty(&mut twice);
+            serializer.pretty_array_indent(indent);
+            serializer.pretty_array_trailing_comma(trailing_comma);
+            again.serialize(&mut serializer).unwrap();
+        }
+        assert_eq!(result, twice);
+    }
+}
+
+#[test]
+fn pretty_multiline_strings_round_trip() {
+    let strings = [
+        "line one\nline two",
+        "\nstarts with a newline",
+        "ends with a newline\n",
+        "\"quoted\"\nacross lines\"",
+        "ends with a backslash\\\nnext",
+        "three quotes \"\"\" and '''\n",
+        "crlf\r\nline",
+        "tab\tand\nnewline",
+    ];
+    for &literal in &[true, false] {
+        for s in strings.iter() {
+            let mut value = toml::value::Table::new();
+            value.insert("s".to_string(), toml::Value::from(*s));
+            let value = toml::Value::Table(value);
+
+            let mut result = String::new();
+            {
+                let mut serializer = toml::Serializer::pretty(&mut result);
+                serializer.pretty_string_literal(literal);
+                value.serialize(&mut serializer).unwrap();
+            }
+            println!("RESULT:\n{}", result);
+            assert!(result.contains("\"\"\"") || result.contains("'''"));
+            let again: toml::Value = toml::from_str(&result).unwrap();
+            assert_eq!(again["s"].as_str(), Some(*s));
+        }
+    }
+}
+
+#[test]
+fn pretty_literal_paths() {
+    fn pretty(s: &str) -> String {
+        let mut value = toml::value::Table::new();
+        value.insert("path".to_string(), toml::Value::from(s));
+        let value = toml::Value::Table(value);
+        let mut result = String::new();
+        value.serialize(&mut toml::Serializer::pretty(&mut result)).unwrap();
+        let again: toml::Value = toml::from_str(&result).unwrap();
+        assert_eq!(again["path"].as_str(), Some(s));
+        result
+    }
+
+    assert_eq!(pretty("C:\\Users\\foo"), "path = 'C:\\Users\\foo'\n");
+    assert_eq!(pretty("C:\\it's"), "path = '''C:\\it's'''\n");
+    assert_eq!(pretty("C:\\bell\u{7}"), "path = \"C:\\\\bell\\u0007\"\n");
+    assert_eq!(pretty("C:\\quote'"), "path = \"C:\\\\quote'\"\n");
+    assert_eq!(pretty("C:\\unit\u{1f}"), "path = \"C:\\\\unit\\u001F\"\n");
+    assert_eq!(pretty("C:\\del\u{7f}"), "path = \"C:\\\\del\\u007F\"\n");
+}
+
+#[test]
+fn pretty_integer_group() {
+    fn grouped(size: usize, n: i64) -> String {
+        let mut value = toml::value::Table::new();
+        value.insert("n".to_string(), toml::Value::Integer(n));
+        let value = toml::Value::Table(value);
+        let mut result = String::new();
+        value
+            .serialize(toml::Serializer::new(&mut result).pretty_integer_group(size))
+            .unwrap();
+        let again: toml::Value = toml::from_str(&result).unwrap();
+        assert_eq!(again["n"].as_integer(), Some(n));
+        result
+    }
+
+    assert_eq!(grouped(0, 1234567), "n = 1234567\n");
+    assert_eq!(grouped(3, 1234567), "n = 1_234_567\n");
+    assert_eq!(grouped(3, -1234567), "n = -1_234_567\n");
+    assert_eq!(grouped(3, 123456), "n = 123_456\n");
+    assert_eq!(grouped(3, 123), "n = 123\n");
+    assert_eq!(grouped(3, -12), "n = -12\n");
+    assert_eq!(grouped(3, 0), "n = 0\n");
+    assert_eq!(grouped(4, 123456789), "n = 1_2345_6789\n");
+    assert_eq!(grouped(1, 105), "n = 1_0_5\n");
+    assert_eq!(grouped(3, i64::min_value()), "n = -9_223_372_036_854_775_808\n");
+}
+
+#[test]
+fn dotted_keys() {
+    fn dotted(toml: &str) -> String {
+        let value: toml::Value = toml::from_str(toml).unwrap();
+        let mut result = String::new();
+        value
+            .serialize(toml::Serializer::new(&mut result).dotted_keys(true))
+            .unwrap();
+        let again: toml::Value = toml::from_str(&result).unwrap();
+        assert_eq!(sort_keys(&again), sort_keys(&value));
+        result
+    }
+
+    assert_eq!(dotted("[a]\nb = 1\n"), "a.b = 1\n");
+    assert_eq!(dotted("[a.b.c]\nd = [1, 2]\n"), "a.b.c.d = [1, 2]\n");
+    assert_eq!(
+        dotted("x = 1\n[a]\n\"b c\" = 1979-05-27\n"),
+        "x = 1\na.\"b c\" = 1979-05-27\n"
+    );
+    assert_eq!(dotted("[a]\nb = 1\nc.d = 2\n"), "[a]\nb = 1\nc.d = 2\n");
+    assert_eq!(
+        dotted("[a]\nb = []\n[z]\nw = 2\ny = 1\n"),
+        "a.b = []\n\n[z]\nw = 2\ny = 1\n"
+    );
+    assert_eq!(dotted("[a]\n[a.b]\n"), "[a.b]\n");
+    assert_eq!(
+        dotted("[t]\nx = 1\ny = 2\n[z]\ne = 1\n"),
+        "z.e = 1\n\n[t]\nx = 1\ny = 2\n"
+    );
+    assert_eq!(
+        dotted("[[a]]\nb.c = 1\n[[a]]\nb.c = 2\n"),
+        "[[a]]\nb.c = 1\n\n[[a]]\nb.c = 2\n"
+    );
+    assert_eq!(dotted("[a]\nb = [{ c = 1 }]\n"), "[[a.b]]\nc = 1\n");
+}
@@ -1,3 +1,4 @@
+extern crate chrono;
 extern crate serde;
 extern crate serde_json;
 extern crate toml;
@@ -256,7 +257,7 @@ fn table_array() {
 
 #[test]
 fn type_errors() {
-    #[derive(Deserialize)]
+    #[derive(Debug, Deserialize)]
     #[allow(dead_code)]
     struct Foo { bar: isize }
 
@@ -268,7 +269,7 @@ fn type_errors() {
         "invalid type: string \"a\", expected isize for key `bar`"
     }
 
-    #[derive(Deserialize)]
+    #[derive(Debug, Deserialize)]
     #[allow(dead_code)]
     struct Bar { foo: Foo }
 
@@ -281,6 +282,13 @@ fn type_errors() {
         }),
         "invalid type: string \"a\", expected isize for key `foo.bar`"
     }
+
+    let err = toml::from_str::<Bar>("[foo]\nbar = true").unwrap_err();
+    assert_eq!(err.type_mismatch(), Some(("isize", "boolean")));
+    let err = Value::Float(1.5).try_into::<Foo>().unwrap_err();
+    assert_eq!(err.type_mismatch(), Some(("struct Foo", "float")));
+    let err = toml::from_str::<Bar>("[foo]").unwrap_err();
+    assert_eq!(err.type_mismatch(), None);
 }
 
 #[test]
@@ -484,6 +492,31 @@ fn empty_arrays() {
     }
 }
 
+#[test]
+fn empty_tables() {
+    #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+    struct Foo { a: i64, map: BTreeMap<String, i64>, nested: Nested }
+    #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+    struct Nested { inner: BTreeMap<String, i64> }
+
+    let foo = Foo { a: 1, map: BTreeMap::new(), nested: Nested { inner: BTreeMap::new() } };
+    equivalent! {
+        foo.clone(),
+        Table(map! {
+            a: Integer(1),
+            map: Table(map! {}),
+            nested: Table(map! { inner: Table(map! {}) })
+        }),
+    }
+
+    let s = t!(toml::to_string(&foo));
+    assert!(s.contains("\n[map]\n"), "{}", s);
+    assert!(s.contains("\n[nested.inner]\n"), "{}", s);
+    assert_eq!(t!(toml::from_str::<Foo>(&s)), foo);
+
+    assert_eq!(t!(toml::to_string(&Table(map! {}))), "");
+}
+
 #[test]
 fn empty_arrays2() {
     #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
@@ -557,6 +590,53 @@ fn newtypes2() {
     }
 }
 
+#[test]
+fn newtype_string() {
+    #[derive(Deserialize, Serialize, PartialEq, Debug, Clone)]
+    struct A {
+        name: Name,
+    }
+
+    #[derive(Deserialize, Serialize, PartialEq, Debug, Clone)]
+    struct Name(String);
+
+    equivalent! {
+        A { name: Name("alpha".to_string()) },
+        Table(map! { name: "alpha" }),
+    }
+}
+
+#[test]
+fn unit_structs() {
+    #[derive(Deserialize, Serialize, PartialEq, Debug, Clone)]
+    struct A {
+        a: Marker,
+        b: Marker,
+    }
+
+    #[derive(Deserialize, Serialize, PartialEq, Debug, Clone)]
+    struct Marker;
+
+    equivalent! {
+        A { a: Marker, b: Marker },
+        Table(map! { a: Table(map! {}), b: Table(map! {}) }),
+    }
+    assert_eq!(t!(toml::to_string(&A { a: Marker, b: Marker })), "[a]\n\n[b]\n");
+    assert_eq!(t!(toml::from_str::<A>("a = {}\n[b]\n")), A { a: Marker, b: Marker });
+    assert_eq!(t!(toml::from_str::<Marker>("")), Marker);
+
+    error! {
+        A,
+        Table(map! { a: Table(map! { x: 1 }), b: Table(map! {}) }),
+        "expected empty table for key `a`"
+    }
+    error! {
+        A,
+        Table(map! { a: Table(map! {}), b: Integer(1) }),
+        "expected unit struct Marker for key `b`"
+    }
+}
+
 #[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
 struct CanBeEmpty {
     a: Option<String>,
@@ -579,6 +659,122 @@ fn table_structs_empty() {
     assert_eq!(toml::to_string(&value).unwrap(), text);
 }
 
+#[test]
+fn values_after_tables() {
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Config {
+        server: Server,
+        name: String,
+        backends: Vec<Server>,
+        ports: Vec<u16>,
+        limits: BTreeMap<String, Limit>,
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Server {
+        tls: Limit,
+        host: String,
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Limit {
+        max: u32,
+    }
+
+    let mut limits = BTreeMap::new();
+    limits.insert("a".to_string(), Limit { max: 1 });
+    let config = Config {
+        server: Server { tls: Limit { max: 2 }, host: "a".to_string() },
+        name: "app".to_string(),
+        backends: vec![
+            Server { tls: Limit { max: 3 }, host: "b".to_string() },
+            Server { tls: Limit { max: 4 }, host: "c".to_string() },
+        ],
+        ports: vec![80, 443],
+        limits: limits,
+    };
+
+    let text = t!(toml::to_string(&config));
+    assert_eq!(
+        text,
+        "\
+name = \"app\"
+ports = [80, 443]
+
+[server]
+host = \"a\"
+
+[server.tls]
+max = 2
+
+[[backends]]
+host = \"b\"
+
+[backends.tls]
+max = 3
+
+[[backends]]
+host = \"c\"
+
+[backends.tls]
+max = 4
+
+[limits.a]
+max = 1
+"
+    );
+    assert_eq!(t!(toml::from_str::<Config>(&text)), config);
+    let pretty = t!(toml::to_string_pretty(&config));
+    assert_eq!(t!(toml::from_str::<Config>(&pretty)), config);
+
+    let mut map = BTreeMap::new();
+    map.insert("a", Value::try_from(&config.server).unwrap());
+    map.insert("b", Value::Integer(1));
+    assert_eq!(t!(toml::to_string(&map)), "b = 1\n\n[a]\nhost = \"a\"\n\n[a.tls]\nmax = 2\n");
+}
+
+#[test]
+fn char_fields() {
+    #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+    struct Foo {
+        delimiter: char,
+        flags: Vec<char>,
+    }
+
+    equivalent! {
+        Foo { delimiter: '\u{e9}', flags: vec!['a', '\''] },
+        Table(map! {
+            delimiter: Value::String("\u{e9}".to_string()),
+            flags: Array(vec![Value::String("a".to_string()), Value::String("'".to_string())])
+        }),
+    }
+
+    error! {
+        Foo,
+        Table(map! {
+            delimiter: Value::String("".to_string()),
+            flags: Array(vec![])
+        }),
+        "invalid value: string \"\", expected a character for key `delimiter`"
+    }
+    error! {
+        Foo,
+        Table(map! {
+            delimiter: Value::String(",".to_string()),
+            flags: Array(vec![Value::String("ab".to_string())])
+        }),
+        "invalid value: string \"ab\", expected a character for key `flags"
+    }
+    error! {
+        Foo,
+        Table(map! {
+            delimiter: Value::Integer(1),
+            flags: Array(vec![])
+        }),
+        "invalid type: integer `1`, expected a character for key `delimiter`"
+    }
+}
+
 #[test]
 fn fixed_size_array() {
     #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
@@ -640,6 +836,49 @@ fn homogeneous_tuple_struct() {
     }
 }
 
+#[test]
+fn tuple_and_array_lengths() {
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct Foo {
+        pair: (String, String),
+        point: [f64; 3],
+    }
+
+    let foo: Foo = t!(toml::from_str("pair = ['a', 'b']\npoint = [1.0, 2.0, 3.0]"));
+    assert_eq!(foo.pair, ("a".to_string(), "b".to_string()));
+    assert_eq!(foo.point, [1.0, 2.0, 3.0]);
+
+    error! {
+        Foo,
+        Table(map! { pair: vec!["a"], point: vec![1.0, 2.0, 3.0] }),
+        "array has too few elements, expected 2 but found 1 for key `pair`"
+    }
+    error! {
+        Foo,
+        Table(map! { pair: vec!["a", "b", "c"], point: vec![1.0, 2.0, 3.0] }),
+        "array has too many elements, expected 2 but found 3 for key `pair`"
+    }
+    error! {
+        Foo,
+        Table(map! { pair: vec!["a", "b"], point: vec![1.0, 2.0] }),
+        "array has too few elements, expected 3 but found 2 for key `point`"
+    }
+    error! {
+        Foo,
+        Table(map! { pair: vec!["a", "b"], point: vec![1.0, 2.0, 3.0, 4.0] }),
+        "array has too many elements, expected 3 but found 4 for key `point`"
+    }
+
+    let value = Table(map! { pair: vec!["a"], point: vec![1.0, 2.0, 3.0] });
+    match Foo::deserialize(&value) {
+        Ok(_) => panic!("successful"),
+        Err(e) => assert_eq!(
+            e.to_string(),
+            "array has too few elements, expected 2 but found 1 for key `pair`"
+        ),
+    }
+}
+
 #[test]
 fn json_interoperability() {
     #[derive(Serialize, Deserialize)]
@@ -651,3 +890,988 @@ fn json_interoperability() {
         {"any":1}
     "#).unwrap();
 }
+
+#[test]
+fn json_value_conversions() {
+    let toml: Value = t!(r#"
+        name = "app"
+        port = 8080
+        ratio = 0.5
+        on = true
+        when = 1979-05-27T07:32:00Z
+        list = [1, 2]
+
+        [nested]
+        deep = "yes"
+    "#.parse());
+
+    let json = t!(toml.clone().into_json());
+    assert_eq!(json["name"], serde_json::json!("app"));
+    assert!(json["port"].is_i64());
+    assert!(json["ratio"].is_f64());
+    assert_eq!(json["on"], serde_json::json!(true));
+    assert_eq!(json["when"], serde_json::json!("1979-05-27T07:32:00Z"));
+    assert_eq!(json["list"], serde_json::json!([1, 2]));
+    assert_eq!(json["nested"]["deep"], serde_json::json!("yes"));
+    assert_eq!(Float(f64::NAN).into_json(), Err(toml::ser::Error::NumberInvalid));
+    assert_eq!(
+        Array(vec![Float(f64::INFINITY)]).into_json(),
+        Err(toml::ser::Error::NumberInvalid)
+    );
+
+    // Datetimes come back as strings, everything else converts losslessly.
+    let back = t!(Value::from_json(json));
+    assert_eq!(back["when"].as_str(), Some("1979-05-27T07:32:00Z"));
+    assert_eq!(back["port"], Integer(8080));
+    assert_eq!(back["ratio"], Float(0.5));
+    assert_eq!(back["nested"], toml["nested"]);
+
+    // `null` object members are dropped, other nulls can't be represented.
+    let json = serde_json::json!({ "a": 1, "b": null });
+    assert_eq!(t!(Value::from_json(json)), Table(map! { a: 1 }));
+    assert!(Value::from_json(serde_json::json!([1, null])).is_err());
+    assert!(Value::from_json(serde_json::Value::Null).is_err());
+    assert_eq!(t!(Value::from_json(serde_json::json!(1e300))), Float(1e300));
+
+    // Anything else TOML can't represent is an error rather than converted
+    // into something close.
+    assert_eq!(
+        Value::from_json(serde_json::json!(u64::max_value())),
+        Err(toml::ser::Error::NumberInvalid)
+    );
+    assert_eq!(
+        Value::from_json(serde_json::json!([1, "a"])),
+        Err(toml::ser::Error::ArrayMixedType)
+    );
+    assert_eq!(
+        Value::from_json(serde_json::json!({ "a": [[1], [1.5, 2.5]] })),
+        Ok(Table(map! { a: Array(vec![
+            Array(vec![Integer(1)]),
+            Array(vec![Float(1.5), Float(2.5)]),
+        ]) }))
+    );
+}
+
+#[test]
+fn deserialize_borrowed_value() {
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct Server<'a> {
+        name: &'a str,
+        port: u16,
+        tags: Vec<String>,
+        kind: Kind,
+    }
+
+    #[derive(Deserialize, PartialEq, Debug)]
+    enum Kind {
+        Primary,
+        Replica,
+    }
+
+    let doc: Value = t!(r#"
+        [servers.alpha]
+        name = "alpha"
+        port = 80
+        tags = ["a", "b"]
+        kind = "Replica"
+    "#.parse());
+
+    let alpha = &doc["servers"]["alpha"];
+    let server = t!(Server::deserialize(alpha));
+    assert_eq!(server, Server {
+        name: "alpha",
+        port: 80,
+        tags: vec!["a".to_string(), "b".to_string()],
+        kind: Kind::Replica,
+    });
+
+    let map = t!(BTreeMap::<&str, Server>::deserialize(&doc["servers"]));
+    assert_eq!(map["alpha"].port, 80);
+
+    // Only `port` has the wrong type, so the error doesn't depend on the
+    // order in which keys are visited.
+    #[derive(Deserialize, Debug)]
+    struct Port {
+        #[allow(dead_code)]
+        port: String,
+    }
+    let err = BTreeMap::<String, Port>::deserialize(&doc["servers"]).unwrap_err();
+    assert!(err.to_string().contains("for key `alpha.port`"), "bad error: {}", err);
+}
+
+#[test]
+fn cow_str_fields() {
+    use std::borrow::Cow;
+
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct Owned<'a> {
+        name: Cow<'a, str>,
+    }
+
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct Borrowed<'a> {
+        #[serde(borrow)]
+        name: Cow<'a, str>,
+        #[serde(borrow)]
+        escaped: Cow<'a, str>,
+    }
+
+    let owned: Owned = t!(toml::from_str("name = 'alpha'"));
+    assert_eq!(owned.name, "alpha");
+    match owned.name {
+        Cow::Owned(_) => {}
+        Cow::Borrowed(_) => panic!("expected an owned string"),
+    }
+
+    let borrowed: Borrowed = t!(toml::from_str("name = 'alpha'\nescaped = \"a\\tb\""));
+    assert_eq!(borrowed.name, "alpha");
+    assert_eq!(borrowed.escaped, "a\tb");
+    match borrowed.name {
+        Cow::Borrowed(_) => {}
+        Cow::Owned(_) => panic!("expected a borrowed string"),
+    }
+
+    let value: Value = t!("name = 'alpha'".parse());
+    assert_eq!(t!(value.clone().try_into::<Owned>()).name, "alpha");
+    assert_eq!(t!(Owned::deserialize(&value)).name, "alpha");
+}
+
+#[test]
+fn error_paths_include_array_indices() {
+    #[derive(Deserialize, Debug)]
+    #[allow(dead_code)]
+    struct Config {
+        servers: Vec<Server>,
+    }
+
+    #[derive(Deserialize, Debug)]
+    #[allow(dead_code)]
+    struct Server {
+        port: u16,
+    }
+
+    error! {
+        Config,
+        "[[servers]]\nport = 1\n[[servers]]\nport = 'x'\n".parse::<Value>().unwrap(),
+        "for key `servers.1.port`"
+    }
+    error! {
+        Config,
+        "servers = [{ port = 1 }, { port = 2 }, { port = -1 }]".parse::<Value>().unwrap(),
+        "for key `servers.2.port`"
+    }
+
+    let doc = "servers = [{ port = 1 }, { port = 'x' }]".parse::<Value>().unwrap();
+    let err = Config::deserialize(&doc).unwrap_err();
+    assert!(err.to_string().contains("for key `servers.1.port`"), "bad error: {}", err);
+}
+
+#[test]
+fn errors_are_std_errors() {
+    use std::error::Error;
+
+    #[derive(Deserialize)]
+    struct Config {
+        port: u16,
+    }
+
+    fn port(s: &str) -> Result<u16, Box<dyn Error>> {
+        let value: Value = s.parse()?;
+        let config: Config = value.try_into()?;
+        Ok(config.port)
+    }
+
+    assert_eq!(port("port = 80").unwrap(), 80);
+    let err = port("port = 'x'").unwrap_err();
+    assert_eq!(err.to_string(), "invalid type: string \"x\", expected u16 for key `port`");
+    let err = port("port =").unwrap_err();
+    assert!(err.to_string().contains("at line 1"), "bad error: {}", err);
+
+    let err: Box<dyn Error> = Box::new(Value::try_from(()).unwrap_err());
+    assert_eq!(err.to_string(), "unsupported Rust type");
+}
+
+#[test]
+fn integer_range_checks() {
+    macro_rules! out_of_range {
+        ($ty:ty, $value:expr) => ({
+            #[derive(Deserialize, Debug)]
+            #[allow(dead_code)]
+            struct Foo {
+                n: $ty,
+            }
+            error! {
+                Foo,
+                Table(map! { n: Integer($value as i64) }),
+                &format!("invalid value: integer `{}`, expected {} for key `n`", $value, stringify!($ty))
+            }
+        })
+    }
+
+    out_of_range!(u8, 256);
+    out_of_range!(u8, -1);
+    out_of_range!(u16, 70000);
+    out_of_range!(u16, -1);
+    out_of_range!(u32, 4294967296i64);
+    out_of_range!(u32, -1);
+    out_of_range!(u64, -1);
+    out_of_range!(i8, 128);
+    out_of_range!(i8, -129);
+    out_of_range!(i16, 32768);
+    out_of_range!(i32, 2147483648i64);
+    out_of_range!(i32, -2147483649i64);
+
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct Bounds {
+        a: u8,
+        b: i8,
+        c: u64,
+        d: i32,
+    }
+    let bounds: Bounds = t!(toml::from_str("a = 255\nb = -128\nc = 9223372036854775807\nd = -2147483648"));
+    assert_eq!(bounds, Bounds { a: 255, b: -128, c: 9223372036854775807, d: -2147483648 });
+}
+
+#[test]
+fn enums_from_value() {
+    #[derive(Deserialize, PartialEq, Debug)]
+    enum E {
+        Unit,
+        Newtype(String),
+        Tuple(i64, i64),
+        Struct { value: i64 },
+    }
+
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct Holder {
+        e: E,
+    }
+
+    fn check(toml: &str, expected: E) {
+        let value: Value = t!(toml.parse());
+        assert_eq!(t!(Holder::deserialize(&value)).e, expected);
+        assert_eq!(t!(value.try_into::<Holder>()).e, expected);
+    }
+
+    check("e = 'Unit'", E::Unit);
+    check("e = { Unit = {} }", E::Unit);
+    check("e = { Newtype = 'x' }", E::Newtype("x".to_string()));
+    check("e = { Tuple = [1, 2] }", E::Tuple(1, 2));
+    check("e = { Tuple = { 0 = 1, 1 = 2 } }", E::Tuple(1, 2));
+    check("e = { Struct = { value = 3 } }", E::Struct { value: 3 });
+    check("[e.Struct]\nvalue = 3", E::Struct { value: 3 });
+
+    fn bad(value: Value, error: &str) {
+        match value.try_into::<Holder>() {
+            Ok(_) => panic!("successful"),
+            Err(e) => assert!(e.to_string().contains(error), "bad error: {}", e),
+        }
+    }
+
+    bad(Table(map! { e: Table(map! {}) }), "expected exactly 1 element, found zero elements");
+    bad(Table(map! { e: Table(map! { Unit: 1 }) }), "expected table, found integer");
+    bad(Table(map! { e: Table(map! { Unit: map! { a: 1 } }) }), "expected empty table");
+    bad(Table(map! { e: Table(map! { Tuple: vec![1] }) }), "expected table with length 2");
+    bad(Table(map! { e: Integer(1) }), "expected string or table");
+}
+
+#[test]
+fn internally_tagged_enums_from_value() {
+    #[derive(Deserialize, PartialEq, Debug)]
+    #[serde(tag = "type", rename_all = "lowercase")]
+    enum Shape {
+        Circle { radius: f64 },
+        Square { side: f64 },
+    }
+
+    let value: Value = t!("type = 'circle'\nradius = 1.5".parse());
+    assert_eq!(t!(value.try_into::<Shape>()), Shape::Circle { radius: 1.5 });
+    let shape: Shape = t!(toml::from_str("type = 'square'\nside = 2.0"));
+    assert_eq!(shape, Shape::Square { side: 2.0 });
+}
+
+#[test]
+fn enums_round_trip() {
+    #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+    enum E {
+        Unit,
+        Newtype(String),
+        Tuple(i64, i64),
+        Struct { value: i64, inner: Inner },
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+    struct Inner {
+        flag: bool,
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Holder {
+        name: String,
+        e: E,
+        list: Vec<E>,
+    }
+
+    let variants = [
+        E::Unit,
+        E::Newtype("x".to_string()),
+        E::Tuple(1, 2),
+        E::Struct { value: 3, inner: Inner { flag: true } },
+    ];
+    for e in variants.iter() {
+        let holder = Holder {
+            name: "a".to_string(),
+            e: e.clone(),
+            // arrays can't mix string (unit) and table variants
+            list: variants[1..].to_vec(),
+        };
+        let toml = t!(toml::to_string(&holder));
+        assert_eq!(t!(toml::from_str::<Holder>(&toml)), holder, "{}", toml);
+
+        let value = t!(Value::try_from(&holder));
+        assert_eq!(t!(value.clone().try_into::<Holder>()), holder);
+        assert_eq!(t!(toml.parse::<Value>()), value);
+    }
+
+    assert_eq!(
+        t!(Value::try_from(E::Newtype("x".to_string()))),
+        Table(map! { Newtype: "x" })
+    );
+    assert_eq!(
+        t!(toml::to_string(&map! { e: E::Newtype("x".to_string()) })),
+        "[e]\nNewtype = \"x\"\n"
+    );
+    assert_eq!(
+        t!(Value::try_from(E::Tuple(1, 2))),
+        Table(map! { Tuple: Array(vec![Integer(1), Integer(2)]) })
+    );
+    assert_eq!(
+        t!(toml::to_string(&map! { e: E::Tuple(1, 2) })),
+        "[e]\nTuple = [1, 2]\n"
+    );
+    assert_eq!(
+        t!(toml::to_string(&map! { e: E::Struct { value: 3, inner: Inner { flag: true } } })),
+        "[e.Struct]\nvalue = 3\n\n[e.Struct.inner]\nflag = true\n"
+    );
+}
+
+#[test]
+fn flatten_unknown_keys() {
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct Inner {
+        b: i64,
+    }
+
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct Foo {
+        a: i64,
+        inner: Inner,
+        #[serde(flatten)]
+        extra: BTreeMap<String, Value>,
+    }
+
+    let toml = "a = 1\nc = 'x'\n[inner]\nb = 2\n[d]\ne = [1, 2]\n";
+    let mut extra = BTreeMap::new();
+    extra.insert("c".to_string(), Value::from("x"));
+    extra.insert("d".to_string(), Table(map! { e: vec![1, 2] }));
+    let expected = Foo { a: 1, inner: Inner { b: 2 }, extra: extra };
+
+    assert_eq!(t!(toml::from_str::<Foo>(toml)), expected);
+    let value: Value = t!(toml.parse());
+    assert_eq!(t!(value.clone().try_into::<Foo>()), expected);
+    assert_eq!(t!(Foo::deserialize(&value)), expected);
+
+    let foo = t!(toml::from_str::<Foo>("a = 1\nwhen = 1979-05-27T07:32:00Z\n[inner]\nb = 2\n"));
+    let when = t!("when = 1979-05-27T07:32:00Z".parse::<Value>())["when"].clone();
+    assert_eq!(foo.extra["when"], when);
+}
+
+#[test]
+fn flatten_captures_only_unconsumed_keys() {
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct Foo {
+        timeout: Option<i64>,
+        name: String,
+        #[serde(flatten)]
+        remaining: BTreeMap<String, Value>,
+    }
+
+    let foo: Foo = t!(toml::from_str("name = 'a'\ntimout = 5"));
+    assert_eq!(foo.timeout, None);
+    assert_eq!(foo.remaining.len(), 1);
+    assert_eq!(foo.remaining["timout"], Integer(5));
+
+    let foo: Foo = t!(toml::from_str("name = 'a'\ntimeout = 5"));
+    assert_eq!(foo.timeout, Some(5));
+    assert!(foo.remaining.is_empty());
+}
+
+#[test]
+fn deny_unknown_fields() {
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct Config {
+        name: String,
+        server: Server,
+        #[serde(default)]
+        backends: Vec<Server>,
+    }
+
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct Server {
+        port: u16,
+        tls: Option<Tls>,
+    }
+
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct Tls {
+        cert: String,
+    }
+
+    fn strict(s: &str) -> Result<Config, toml::de::Error> {
+        let mut d = toml::Deserializer::new(s);
+        d.set_deny_unknown_fields(true);
+        Config::deserialize(&mut d)
+    }
+
+    fn bad(s: &str, error: &str) {
+        match strict(s) {
+            Ok(_) => panic!("successful"),
+            Err(e) => assert!(e.to_string().contains(error), "bad error: {}", e),
+        }
+        assert!(toml::from_str::<Config>(s).is_ok());
+    }
+
+    let ok = "name = 'a'\n[server]\nport = 1\ntls = { cert = 'c' }\n[[backends]]\nport = 2\n";
+    assert_eq!(t!(strict(ok)), t!(toml::from_str::<Config>(ok)));
+
+    bad(
+        "name = 'a'\ntimout = 5\n[server]\nport = 1\n",
+        "unexpected keys in table: `[\"timout\"]`, available keys: `[\"name\", \"server\", \"backends\"]` at line 2",
+    );
+    bad("name = 'a'\n[server]\nport = 1\nhost = 'x'\n", "for key `server` at line 4");
+    bad("name = 'a'\n[server]\nport = 1\n[server.extra]\n", "for key `server` at line 4");
+    bad("name = 'a'\nserver = { port = 1, tls = { cert = 'c', key = 'k' } }\n", "for key `server.tls`");
+    bad("name = 'a'\n[server]\nport = 1\n[[backends]]\nport = 2\n[[backends]]\nport = 3\nx = 1\n", "for key `backends.1` at line 8");
+}
+
+#[test]
+fn ignore_key_case() {
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct Config {
+        name: String,
+        server: Server,
+        #[serde(default)]
+        backends: Vec<Server>,
+    }
+
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct Server {
+        port: u16,
+        tls: Option<Tls>,
+    }
+
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct Tls {
+        cert: String,
+    }
+
+    fn relaxed(s: &str) -> Result<Config, toml::de::Error> {
+        let mut d = toml::Deserializer::new(s);
+        d.set_ignore_key_case(true);
+        Config::deserialize(&mut d)
+    }
+
+    let exact = "name = 'a'\n[server]\nport = 1\ntls = { cert = 'c' }\n[[backends]]\nport = 2\n";
+    let mixed = "Name = 'a'\n[SERVER]\nPort = 1\nTLS = { Cert = 'c' }\n[[Backends]]\nPORT = 2\n";
+    assert_eq!(t!(relaxed(mixed)), t!(toml::from_str::<Config>(exact)));
+    assert!(toml::from_str::<Config>(mixed).is_err());
+    assert_eq!(t!(relaxed("name = 'a'\nServer.Port = 1\n")).server.port, 1);
+
+    let err = relaxed("name = 'a'\nNAME = 'b'\n[server]\nport = 1\n").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "keys `name` and `NAME` both match field `name` when ignoring case at line 2"
+    );
+    let err = relaxed("name = 'a'\n[Server]\nport = 1\n[server]\nport = 2\n").unwrap_err();
+    assert!(err.to_string().contains("keys `Server` and `server`"), "bad error: {}", err);
+    let err = relaxed("name = 'a'\nserver = { port = 1, Port = 2 }\n").unwrap_err();
+    assert!(err.to_string().contains("keys `port` and `Port`"), "bad error: {}", err);
+
+    // Maps are not structs, so their keys keep their case.
+    let map: BTreeMap<String, i32> = {
+        let mut d = toml::Deserializer::new("a = 1\nA = 2\n");
+        d.set_ignore_key_case(true);
+        t!(BTreeMap::deserialize(&mut d))
+    };
+    assert_eq!(map.len(), 2);
+
+    let mut d = toml::Deserializer::new("Name = 'a'\nport = 1\n[server]\nport = 1\n");
+    d.set_ignore_key_case(true);
+    d.set_deny_unknown_fields(true);
+    let err = Config::deserialize(&mut d).unwrap_err();
+    assert!(err.to_string().contains("`[\"port\"]`"), "bad error: {}", err);
+}
+
+#[test]
+fn coerce_strings() {
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct Config {
+        port: u16,
+        debug: bool,
+        ratio: f64,
+        name: String,
+        limits: Vec<u32>,
+    }
+
+    fn coerced(s: &str) -> Result<Config, toml::de::Error> {
+        let mut d = toml::Deserializer::new(s);
+        d.set_coerce_strings(true);
+        Config::deserialize(&mut d)
+    }
+
+    let strings = "port = '8080'\ndebug = 'true'\nratio = '0.5'\nname = '1'\nlimits = ['1', '2']\n";
+    let typed = "port = 8080\ndebug = true\nratio = 0.5\nname = '1'\nlimits = [1, 2]\n";
+    assert_eq!(t!(coerced(strings)), t!(toml::from_str::<Config>(typed)));
+    assert_eq!(t!(coerced(typed)), t!(toml::from_str::<Config>(typed)));
+    assert!(toml::from_str::<Config>(strings).is_err());
+
+    let err = coerced("port = '80a'\ndebug = true\nratio = 1.0\nname = ''\nlimits = []\n")
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "invalid value: string \"80a\", expected u16 for key `port`"
+    );
+    let err = coerced("port = '70000'\ndebug = true\nratio = 1.0\nname = ''\nlimits = []\n")
+        .unwrap_err();
+    assert!(err.to_string().contains("expected u16"), "bad error: {}", err);
+}
+
+#[test]
+fn arrays_of_tables_use_headers() {
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Doc {
+        empty: Vec<Item>,
+        items: Vec<Item>,
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Item {
+        name: String,
+        parts: Vec<Part>,
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Part {
+        id: i64,
+    }
+
+    let doc = Doc {
+        empty: vec![],
+        items: vec![
+            Item { name: "a".to_string(), parts: vec![Part { id: 1 }, Part { id: 2 }] },
+            Item { name: "b".to_string(), parts: vec![] },
+        ],
+    };
+
+    for &pretty in &[false, true] {
+        let s = if pretty {
+            t!(toml::to_string_pretty(&doc))
+        } else {
+            t!(toml::to_string(&doc))
+        };
+        assert!(s.starts_with("empty = []\n"), "{}", s);
+        assert_eq!(s.matches("[[items]]").count(), 2, "{}", s);
+        assert_eq!(s.matches("[[items.parts]]").count(), 2, "{}", s);
+        assert!(!s.contains('{'), "{}", s);
+        assert_eq!(t!(toml::from_str::<Doc>(&s)), doc);
+    }
+
+    let mixed = Table(map! { a: Array(vec![Integer(1), Table(map! {})]) });
+    assert_eq!(
+        toml::to_string(&mixed).unwrap_err().to_string(),
+        "arrays cannot have mixed types"
+    );
+    let mixed = Table(map! { a: Array(vec![Table(map! {}), Integer(1)]) });
+    assert!(toml::to_string(&mixed).is_err());
+}
+
+#[test]
+fn parsed_map_keys() {
+    use std::collections::HashMap;
+
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct Config {
+        ports: HashMap<u16, String>,
+        flags: BTreeMap<bool, i64>,
+        offsets: BTreeMap<i8, i8>,
+    }
+
+    let toml = "offsets = { -1 = 1, 2 = -2 }\n[ports]\n80 = 'http'\n443 = 'https'\n[flags]\ntrue = 1\nfalse = 0\n";
+    let mut ports = HashMap::new();
+    ports.insert(80, "http".to_string());
+    ports.insert(443, "https".to_string());
+    let mut flags = BTreeMap::new();
+    flags.insert(true, 1);
+    flags.insert(false, 0);
+    let mut offsets = BTreeMap::new();
+    offsets.insert(-1, 1);
+    offsets.insert(2, -2);
+    let expected = Config { ports: ports, flags: flags, offsets: offsets };
+
+    assert_eq!(t!(toml::from_str::<Config>(toml)), expected);
+    let value: Value = t!(toml.parse());
+    assert_eq!(t!(Config::deserialize(&value)), expected);
+    assert_eq!(t!(value.try_into::<Config>()), expected);
+
+    error! {
+        BTreeMap<String, BTreeMap<u16, String>>,
+        "[ports]\nhttp = 'x'".parse::<Value>().unwrap(),
+        "invalid value: string \"http\", expected u16 for key `ports`"
+    }
+    error! {
+        BTreeMap<String, BTreeMap<u8, String>>,
+        "[ports]\n443 = 'x'".parse::<Value>().unwrap(),
+        "invalid value: string \"443\", expected u8 for key `ports`"
+    }
+}
+
+#[test]
+fn serialize_map_keys() {
+    use std::collections::HashMap;
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Config {
+        ports: HashMap<u32, String>,
+        flags: BTreeMap<bool, i64>,
+        offsets: BTreeMap<i8, i8>,
+        letters: BTreeMap<char, u64>,
+    }
+
+    let mut ports = HashMap::new();
+    ports.insert(80, "http".to_string());
+    ports.insert(4_000_000_000, "custom".to_string());
+    let mut flags = BTreeMap::new();
+    flags.insert(true, 1);
+    let mut offsets = BTreeMap::new();
+    offsets.insert(-1, 1);
+    offsets.insert(2, -2);
+    let mut letters = BTreeMap::new();
+    letters.insert('a', 1);
+    letters.insert('.', 2);
+    let config = Config { ports: ports, flags: flags, offsets: offsets, letters: letters };
+
+    let toml = t!(toml::to_string(&config));
+    assert!(toml.starts_with("[ports]\n"), "{}", toml);
+    assert!(toml.contains("\n4000000000 = \"custom\"\n"), "{}", toml);
+    assert!(toml.contains("\n-1 = 1\n"), "{}", toml);
+    assert!(toml.contains("\n\".\" = 2\n"), "{}", toml);
+    assert_eq!(t!(toml::from_str::<Config>(&toml)), config);
+
+    let value = t!(Value::try_from(&config));
+    assert_eq!(value["ports"]["80"].as_str(), Some("http"));
+    assert_eq!(value["flags"]["true"].as_integer(), Some(1));
+    assert_eq!(t!(value.try_into::<Config>()), config);
+
+    let mut map = HashMap::new();
+    map.insert(vec![1], 1);
+    assert!(toml::to_string(&map).is_err());
+    assert!(Value::try_from(&map).is_err());
+}
+
+#[test]
+fn serialize_none_omits_key() {
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Server {
+        host: Option<String>,
+        port: Option<u16>,
+        tls: Option<Tls>,
+    }
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Tls {
+        cert: String,
+        key: Option<String>,
+    }
+
+    let server = Server {
+        host: None,
+        port: Some(8080),
+        tls: Some(Tls { cert: "a.pem".to_string(), key: None }),
+    };
+    let toml = t!(toml::to_string(&server));
+    assert_eq!(toml, "port = 8080\n\n[tls]\ncert = \"a.pem\"\n");
+    assert_eq!(t!(toml::from_str::<Server>(&toml)), server);
+
+    let mut map = BTreeMap::new();
+    map.insert("a", None);
+    map.insert("b", Some(1));
+    let toml = t!(toml::to_string(&map));
+    assert_eq!(toml, "b = 1\n");
+    assert_eq!(t!(toml::from_str::<BTreeMap<String, Option<i64>>>(&toml)).len(), 1);
+
+    let value = t!(Value::try_from(&server));
+    assert!(value.get("host").is_none());
+    assert!(value["tls"].get("key").is_none());
+}
+
+#[test]
+fn root_array_into_vec() {
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct Server {
+        host: String,
+        port: u16,
+    }
+
+    let value: Value = t!("[[servers]]\nhost = 'a'\nport = 1\n[[servers]]\nhost = 'b'\nport = 2\n".parse());
+    let servers = value["servers"].clone();
+    let expected = vec![
+        Server { host: "a".to_string(), port: 1 },
+        Server { host: "b".to_string(), port: 2 },
+    ];
+    assert_eq!(t!(Vec::<Server>::deserialize(&servers)), expected);
+    assert_eq!(t!(servers.try_into::<Vec<Server>>()), expected);
+
+    let empty = Array(vec![]);
+    assert_eq!(t!(Vec::<Server>::deserialize(&empty)), vec![]);
+    assert_eq!(t!(empty.try_into::<Vec<Server>>()), vec![]);
+
+    let partial = Array(vec![Table(map! { host: Value::String("a".to_string()) })]);
+    assert_eq!(
+        partial.try_into::<Vec<Server>>().unwrap_err().to_string(),
+        "missing field `port` for key `0`"
+    );
+}
+
+#[test]
+fn classify_errors() {
+    use toml::de::Category;
+
+    #[derive(Deserialize, Debug)]
+    struct Config {
+        #[allow(dead_code)]
+        port: u16,
+    }
+
+    fn classify(s: &str) -> Category {
+        toml::from_str::<Config>(s).unwrap_err().classify()
+    }
+
+    assert_eq!(classify("port = @"), Category::Syntax);
+    assert_eq!(classify("port = [1, 'a']"), Category::Syntax);
+    assert_eq!(classify("port = 1\nport = 2"), Category::Syntax);
+    assert_eq!(classify("[a]\n[a]"), Category::Syntax);
+    assert_eq!(classify("port = ="), Category::Syntax);
+    assert_eq!(toml::from_slice::<Config>(b"port = \"\xff\"").unwrap_err().classify(), Category::Syntax);
+
+    assert_eq!(classify(""), Category::Data);
+    assert_eq!(classify("port = 'a'"), Category::Data);
+    assert_eq!(classify("port = 70000"), Category::Data);
+    assert_eq!(classify("port = {}"), Category::Data);
+}
+
+#[test]
+fn integers_widen_to_floats() {
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct Shape {
+        scale: f64,
+        ratio: f32,
+    }
+
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct Count {
+        n: i64,
+    }
+
+    let expected = Shape { scale: 3.0, ratio: -2.0 };
+    assert_eq!(t!(toml::from_str::<Shape>("scale = 3\nratio = -2")), expected);
+    assert_eq!(t!(toml::from_str::<Shape>("scale = 3.0\nratio = -2.0")), expected);
+    let value = Table(map! { scale: Integer(3), ratio: Integer(-2) });
+    assert_eq!(t!(Shape::deserialize(&value)), expected);
+    assert_eq!(t!(value.try_into::<Shape>()), expected);
+
+    error! {
+        Count,
+        Table(map! { n: Float(3.0) }),
+        "expected i64 for key `n`"
+    }
+}
+
+#[test]
+fn missing_table_with_default() {
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct Config {
+        name: String,
+        #[serde(default)]
+        log: Log,
+    }
+
+    #[derive(Deserialize, PartialEq, Debug, Default)]
+    struct Log {
+        level: Option<String>,
+        #[serde(default)]
+        verbose: bool,
+    }
+
+    #[derive(Deserialize, Debug)]
+    struct Strict {
+        #[allow(dead_code)]
+        log: Log,
+    }
+
+    let config = t!(toml::from_str::<Config>("name = 'app'"));
+    assert_eq!(config.log, Log { level: None, verbose: false });
+    let config = t!(toml::from_str::<Config>("name = 'app'\n[log]\n"));
+    assert_eq!(config.log, Log::default());
+    let config = t!(toml::from_str::<Config>("name = 'app'\n[log]\nverbose = true\n"));
+    assert_eq!(config.log, Log { level: None, verbose: true });
+
+    let value: Value = t!("name = 'app'".parse());
+    assert_eq!(t!(value.try_into::<Config>()).log, Log::default());
+
+    let err = toml::from_str::<Strict>("").unwrap_err();
+    assert_eq!(err.to_string(), "missing field `log`");
+}
+
+#[test]
+fn absent_fields_use_serde_defaults() {
+    fn default_port() -> u16 {
+        8080
+    }
+
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct Config {
+        name: String,
+        #[serde(default)]
+        workers: u32,
+        #[serde(default)]
+        tags: Vec<String>,
+        #[serde(default = "default_port")]
+        port: u16,
+    }
+
+    let config = t!(toml::from_str::<Config>("name = 'app'"));
+    assert_eq!(
+        config,
+        Config { name: "app".to_string(), workers: 0, tags: vec![], port: 8080 }
+    );
+    let config = t!(toml::from_str::<Config>("name = 'app'\nworkers = 4\nport = 80"));
+    assert_eq!(config.workers, 4);
+    assert_eq!(config.port, 80);
+
+    error! {
+        Config,
+        Table(map! { workers: Integer(4) }),
+        "missing field `name`"
+    }
+}
+
+#[test]
+fn chrono_fields() {
+    use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, Utc};
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Times {
+        utc: DateTime<Utc>,
+        offset: DateTime<FixedOffset>,
+        local: NaiveDateTime,
+        date: NaiveDate,
+    }
+
+    let toml = "utc = 1979-05-27T07:32:00Z\n\
+                offset = 1979-05-27T00:32:00.5-07:00\n\
+                local = 1979-05-27 07:32:00\n\
+                date = 1979-05-27\n";
+    let expected = Times {
+        utc: t!("1979-05-27T07:32:00Z".parse()),
+        offset: t!("1979-05-27T00:32:00.5-07:00".parse()),
+        local: t!("1979-05-27T07:32:00".parse()),
+        date: t!("1979-05-27".parse()),
+    };
+
+    assert_eq!(t!(toml::from_str::<Times>(toml)), expected);
+    let value: Value = t!(toml.parse());
+    assert_eq!(t!(value.clone().try_into::<Times>()), expected);
+    assert_eq!(t!(Times::deserialize(&value)), expected);
+    assert_eq!(t!(toml::from_str::<Times>(&t!(toml::to_string(&expected)))), expected);
+
+    let err = toml::from_str::<Times>(&toml.replace("07:32:00Z", "07:32:00")).unwrap_err();
+    assert!(err.to_string().starts_with("invalid datetime `1979-05-27T07:32:00`: "), "bad error: {}", err);
+    assert!(err.to_string().ends_with(" for key `utc`"), "bad error: {}", err);
+}
+
+#[test]
+fn ignored_any() {
+    use serde::de::{self, IgnoredAny, MapAccess, SeqAccess};
+    use std::fmt;
+
+    // Records whether the deserializer handed over the contents of a value it
+    // was asked to ignore.
+    #[derive(Debug, PartialEq)]
+    struct Skipped(&'static str);
+
+    impl<'de> Deserialize<'de> for Skipped {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Skipped, D::Error> {
+            struct SkippedVisitor;
+
+            impl<'de> de::Visitor<'de> for SkippedVisitor {
+                type Value = Skipped;
+
+                fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                    f.write_str("anything")
+                }
+
+                fn visit_unit<E>(self) -> Result<Skipped, E> {
+                    Ok(Skipped("skipped"))
+                }
+
+                fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Skipped, A::Error> {
+                    while let Some(IgnoredAny) = seq.next_element()? {}
+                    Ok(Skipped("walked"))
+                }
+
+                fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Skipped, A::Error> {
+                    while let Some((IgnoredAny, IgnoredAny)) = map.next_entry()? {}
+                    Ok(Skipped("walked"))
+                }
+            }
+
+            deserializer.deserialize_ignored_any(SkippedVisitor)
+        }
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Partial {
+        name: String,
+        array: Skipped,
+        inline: Skipped,
+        big: IgnoredAny,
+    }
+
+    let toml = r#"
+        name = "app"
+        array = [[1, 2], [3]]
+        inline = { a = [1979-05-27, 1979-05-28], b = { c = "d" } }
+        big = [{ x = 1 }, { x = 2 }]
+        unknown = [1, 2]
+
+        [servers.alpha]
+        ip = "10.0.0.1"
+
+        [[deps]]
+        name = "serde"
+    "#;
+
+    let partial: Partial = t!(toml::from_str(toml));
+    assert_eq!(partial.name, "app");
+    assert_eq!(partial.array, Skipped("skipped"));
+    assert_eq!(partial.inline, Skipped("skipped"));
+
+    let value: Value = t!(toml.parse());
+    let partial: Partial = t!(value.clone().try_into());
+    assert_eq!(partial.array, Skipped("skipped"));
+    let partial = t!(Partial::deserialize(&value));
+    assert_eq!(partial.inline, Skipped("skipped"));
+
+    t!(toml::from_str::<IgnoredAny>(toml));
+    t!(value.try_into::<IgnoredAny>());
+    assert!(toml::from_str::<IgnoredAny>("a = 1\na = 2").is_err());
+}
@@ -0,0 +1,45 @@
+extern crate toml;
+
+use std::fs;
+use std::path::Path;
+
+// Every prefix of every fixture must parse to either a value or an error;
+// none of them may panic. This is a cheap, deterministic stand-in for the
+// fuzz target in `fuzz/`.
+fn check_prefixes(dir: &str) {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join(dir);
+    for entry in fs::read_dir(&dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().map_or(true, |ext| ext != "toml") {
+            continue;
+        }
+        let input = fs::read_to_string(&path).unwrap();
+        for (end, _) in input.char_indices() {
+            let _ = input[..end].parse::<toml::Value>();
+            let _ = toml::de::from_str_recover::<toml::Value>(&input[..end]);
+            let _ = toml::from_slice::<toml::Value>(&input.as_bytes()[..end + 1]);
+        }
+    }
+}
+
+#[test]
+fn valid_prefixes() {
+    check_prefixes("tests/valid");
+}
+
+#[test]
+fn invalid_prefixes() {
+    check_prefixes("tests/invalid");
+}
+
+#[test]
+fn deep_nesting() {
+    let arrays = format!("a = {}", "[".repeat(100_000));
+    assert!(arrays.parse::<toml::Value>().is_err());
+
+    let tables = format!("a = {}", "{b = ".repeat(100_000));
+    assert!(tables.parse::<toml::Value>().is_err());
+
+    let header = format!("[{}]", vec!["a"; 100_000].join("."));
+    assert!(header.parse::<toml::Value>().is_err());
+}
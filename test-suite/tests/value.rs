@@ -0,0 +1,1122 @@
+extern crate serde;
+extern crate toml;
+
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
+
+use toml::value::Table;
+use toml::Value;
+
+fn doc() -> Value {
+    r#"
+        title = "example"
+
+        [servers.alpha]
+        ip = "10.0.0.1"
+
+        [[deps]]
+        name = "serde"
+
+        [[deps]]
+        name = "chrono"
+    "#
+    .parse()
+    .unwrap()
+}
+
+// Puts the keys of every table in sorted order, so that values can be compared
+// whether or not the `preserve_order` feature is enabled.
+fn sort_keys(value: &Value) -> Value {
+    match *value {
+        Value::Table(ref table) => {
+            let mut entries = table.iter().collect::<Vec<_>>();
+            entries.sort_by(|a, b| a.0.cmp(b.0));
+            Value::Table(
+                entries
+                    .into_iter()
+                    .map(|(key, value)| (key.clone(), sort_keys(value)))
+                    .collect(),
+            )
+        }
+        Value::Array(ref array) => Value::Array(array.iter().map(sort_keys).collect()),
+        ref other => other.clone(),
+    }
+}
+
+#[test]
+fn parse() {
+    let value = Value::parse("title = 'x'\n[servers.alpha]\nip = '10.0.0.1'").unwrap();
+    assert!(value.is_table());
+    assert_eq!(value.lookup("servers.alpha.ip").and_then(|v| v.as_str()), Some("10.0.0.1"));
+    assert_eq!(Value::parse("").unwrap(), Value::Table(Table::new()));
+
+    let errors = Value::parse("a = @\nb = 1\n[c]\n[c]\nd = [1, 'x']").unwrap_err();
+    let lines = errors
+        .iter()
+        .map(|e| e.line_col().unwrap().0)
+        .collect::<Vec<_>>();
+    assert_eq!(lines, [0, 3, 4]);
+}
+
+#[test]
+fn lookup() {
+    let v = doc();
+    assert_eq!(v.lookup("title").and_then(|v| v.as_str()), Some("example"));
+    assert_eq!(
+        v.lookup("servers.alpha.ip").and_then(|v| v.as_str()),
+        Some("10.0.0.1")
+    );
+    assert_eq!(
+        v.lookup("deps.1.name").and_then(|v| v.as_str()),
+        Some("chrono")
+    );
+    assert_eq!(v.lookup(""), Some(&v));
+
+    assert_eq!(v.lookup("servers.beta"), None);
+    assert_eq!(v.lookup("deps.2.name"), None);
+    assert_eq!(v.lookup("deps.name"), None);
+    assert_eq!(v.lookup("title.0"), None);
+    assert_eq!(v.lookup("servers..ip"), None);
+}
+
+#[test]
+fn get_path_or() {
+    let v = doc();
+    let default = Value::from("none");
+    assert_eq!(v.get_path_or("title", &default).as_str(), Some("example"));
+    assert_eq!(
+        v.get_path_or("deps.1.name", &default).as_str(),
+        Some("chrono")
+    );
+    assert_eq!(v.get_path_or("", &default), &v);
+
+    assert_eq!(v.get_path_or("servers.beta", &default), &default);
+    assert_eq!(v.get_path_or("deps.2.name", &default), &default);
+    assert_eq!(v.get_path_or("title.0", &default), &default);
+}
+
+#[test]
+fn lookup_mut() {
+    let mut v = doc();
+    *v.lookup_mut("deps.0.name").unwrap() = Value::Integer(5);
+    assert_eq!(v["deps"][0]["name"].as_integer(), Some(5));
+
+    *v.lookup_mut("servers.alpha").unwrap() = Value::from("gone");
+    assert_eq!(v.lookup("servers.alpha.ip"), None);
+    assert_eq!(v.lookup("servers.alpha").and_then(|v| v.as_str()), Some("gone"));
+
+    assert!(v.lookup_mut("deps.2").is_none());
+    assert!(v.lookup_mut("deps.x").is_none());
+    assert!(v.lookup_mut("").unwrap().is_table());
+}
+
+#[test]
+fn pointer() {
+    let v = doc();
+    assert_eq!(v.pointer("/title").and_then(|v| v.as_str()), Some("example"));
+    assert_eq!(
+        v.pointer("/servers/alpha/ip").and_then(|v| v.as_str()),
+        Some("10.0.0.1")
+    );
+    assert_eq!(
+        v.pointer("/deps/1/name").and_then(|v| v.as_str()),
+        Some("chrono")
+    );
+    assert_eq!(v.pointer(""), Some(&v));
+
+    assert_eq!(v.pointer("title"), None);
+    assert_eq!(v.pointer("/servers/beta"), None);
+    assert_eq!(v.pointer("/deps/2/name"), None);
+    assert_eq!(v.pointer("/deps/01/name"), None);
+    assert_eq!(v.pointer("/deps/+1/name"), None);
+    assert_eq!(v.pointer("/title/0"), None);
+
+    let v: Value = r#"
+        "a.b" = 1
+        "c/d" = 2
+        "e~f" = 3
+        "~1" = 4
+    "#
+    .parse()
+    .unwrap();
+    assert_eq!(v.pointer("/a.b").and_then(|v| v.as_integer()), Some(1));
+    assert_eq!(v.pointer("/c~1d").and_then(|v| v.as_integer()), Some(2));
+    assert_eq!(v.pointer("/e~0f").and_then(|v| v.as_integer()), Some(3));
+    assert_eq!(v.pointer("/~01").and_then(|v| v.as_integer()), Some(4));
+}
+
+#[test]
+fn pointer_mut() {
+    let mut v = doc();
+    *v.pointer_mut("/deps/0/name").unwrap() = Value::Integer(5);
+    assert_eq!(v["deps"][0]["name"].as_integer(), Some(5));
+
+    assert!(v.pointer_mut("/deps/2").is_none());
+    assert!(v.pointer_mut("deps").is_none());
+    assert!(v.pointer_mut("").unwrap().is_table());
+}
+
+#[test]
+fn take() {
+    let mut v = doc();
+    let alpha = v.take("servers.alpha").unwrap();
+    assert_eq!(alpha["ip"].as_str(), Some("10.0.0.1"));
+    assert_eq!(v.lookup("servers.alpha"), None);
+    assert!(v["servers"].as_table().unwrap().is_empty());
+
+    let first = v.take("deps.0").unwrap();
+    assert_eq!(first["name"].as_str(), Some("serde"));
+    assert_eq!(v["deps"].as_array().unwrap().len(), 1);
+    assert_eq!(v.lookup("deps.0.name").and_then(|v| v.as_str()), Some("chrono"));
+
+    let before = v.clone();
+    assert_eq!(v.take("deps.1"), None);
+    assert_eq!(v.take("deps.x"), None);
+    assert_eq!(v.take("title.x"), None);
+    assert_eq!(v.take("missing"), None);
+    assert_eq!(v.take(""), None);
+    assert_eq!(v, before);
+
+    assert_eq!(v.take("title").and_then(|v| v.as_str().map(String::from)), Some("example".to_string()));
+    assert_eq!(v.get("title"), None);
+}
+
+#[test]
+fn remove() {
+    let mut v = doc();
+    let servers = v.remove("servers").unwrap();
+    assert!(servers["alpha"].is_table());
+    assert_eq!(v.get("servers"), None);
+    assert_eq!(v.remove("servers"), None);
+    assert_eq!(v.remove("title.x"), None);
+    assert_eq!(Value::from(1).remove("a"), None);
+}
+
+#[test]
+fn insert() {
+    let mut v = doc();
+    assert_eq!(v.insert("servers.beta.ip", Value::from("10.0.0.2")), Ok(None));
+    assert_eq!(v.lookup("servers.beta.ip").and_then(|v| v.as_str()), Some("10.0.0.2"));
+    assert_eq!(
+        v.insert("servers.alpha.ip", Value::from("10.0.0.3")),
+        Ok(Some(Value::from("10.0.0.1")))
+    );
+    assert_eq!(v.insert("a.b.c", Value::from(1)), Ok(None));
+    assert!(v["a"]["b"].is_table());
+
+    let before = v.clone();
+    let err = v.insert("title.x.y", Value::from(1)).unwrap_err();
+    assert_eq!(err.path(), "title");
+    assert_eq!(err.to_string(), "cannot insert into `title`, which is of type string");
+    let err = v.insert("deps.0.name", Value::from(1)).unwrap_err();
+    assert_eq!(err.path(), "deps");
+    assert_eq!(v, before);
+
+    let mut flat = Value::Table(Table::new());
+    for &(key, value) in &[("db.host", "localhost"), ("db.user", "admin"), ("name", "app")] {
+        flat.insert(key, Value::from(value)).unwrap();
+    }
+    let expected: Value = "name = 'app'\n[db]\nhost = 'localhost'\nuser = 'admin'".parse().unwrap();
+    assert_eq!(sort_keys(&flat), sort_keys(&expected));
+
+    let mut scalar = Value::from(1);
+    assert_eq!(
+        scalar.insert("a", Value::from(2)).unwrap_err().to_string(),
+        "cannot insert into a value of type integer"
+    );
+    assert_eq!(scalar.insert("", Value::from(2)), Ok(Some(Value::from(1))));
+    assert_eq!(scalar, Value::from(2));
+}
+
+#[test]
+fn index() {
+    let v = doc();
+    assert_eq!(v["servers"]["alpha"]["ip"].as_str(), Some("10.0.0.1"));
+    assert_eq!(v["deps"][1]["name"].as_str(), Some("chrono"));
+    assert_eq!(
+        v.get("servers").and_then(|v| v.get("beta")).and_then(|v| v.get("ip")),
+        None
+    );
+}
+
+#[test]
+fn get() {
+    let mut v = doc();
+    assert_eq!(v.get("title").and_then(|v| v.as_str()), Some("example"));
+    assert_eq!(v["deps"].get(0).and_then(|v| v.get("name")), Some(&Value::from("serde")));
+    assert_eq!(v.get(&"title".to_string()), v.get("title"));
+
+    assert_eq!(v.get("missing"), None);
+    assert_eq!(v["deps"].get(2), None);
+    assert_eq!(v.get(0), None);
+    assert_eq!(v["deps"].get("name"), None);
+    assert_eq!(v["title"].get("x"), None);
+    assert_eq!(v["title"].get(0), None);
+
+    *v.get_mut("title").unwrap() = Value::Integer(1);
+    assert_eq!(v["title"].as_integer(), Some(1));
+    *v["deps"].get_mut(1).unwrap() = Value::Boolean(true);
+    assert_eq!(v["deps"][1].as_bool(), Some(true));
+    assert!(v["deps"].get_mut(2).is_none());
+    assert!(v["title"].get_mut("x").is_none());
+    assert!(v.get_mut(0).is_none());
+}
+
+#[test]
+#[should_panic]
+fn index_missing_key() {
+    let v = doc();
+    let _ = &v["servers"]["beta"];
+}
+
+#[test]
+#[should_panic]
+fn index_wrong_type() {
+    let v = doc();
+    let _ = &v["title"][0];
+}
+
+#[test]
+fn accessors() {
+    let v: Value = r#"
+        s = "str"
+        i = 1
+        f = 1.5
+        b = true
+        d = 1979-05-27T07:32:00Z
+        a = [1]
+        t = {}
+    "#
+    .parse()
+    .unwrap();
+
+    assert_eq!(v["s"].as_str(), Some("str"));
+    assert_eq!(v["i"].as_integer(), Some(1));
+    assert_eq!(v["f"].as_float(), Some(1.5));
+    assert_eq!(v["b"].as_bool(), Some(true));
+    assert_eq!(
+        v["d"].as_datetime().map(|d| d.to_string()),
+        Some("1979-05-27T07:32:00Z".to_string())
+    );
+    assert_eq!(v["a"].as_array().map(|a| a.len()), Some(1));
+    assert_eq!(v["t"].as_table().map(|t| t.len()), Some(0));
+
+    assert_eq!(v["i"].as_str(), None);
+    assert_eq!(v["s"].as_integer(), None);
+    assert_eq!(v["i"].as_float(), None);
+    assert_eq!(v["s"].as_bool(), None);
+    assert_eq!(v["s"].as_datetime(), None);
+    assert_eq!(v["t"].as_array(), None);
+    assert_eq!(v["a"].as_table(), None);
+}
+
+#[test]
+fn numeric_accessors() {
+    let v: Value = "i = 2\nf = 2.0\ns = '2'".parse().unwrap();
+
+    assert_eq!(v["i"].as_integer(), Some(2));
+    assert_eq!(v["f"].as_integer(), None);
+    assert_eq!(v["s"].as_integer(), None);
+
+    assert_eq!(v["i"].as_number_f64(), Some(2.0));
+    assert_eq!(v["f"].as_number_f64(), Some(2.0));
+    assert_eq!(v["s"].as_number_f64(), None);
+}
+
+#[test]
+fn into_accessors() {
+    let v = doc();
+    assert_eq!(v["title"].clone().into_string(), Some("example".to_string()));
+    assert_eq!(v["title"].clone().into_array(), None);
+
+    let deps = v["deps"].clone().into_array().unwrap();
+    assert_eq!(deps.len(), 2);
+    assert_eq!(v["deps"].clone().into_table(), None);
+
+    let table = v.into_table().unwrap();
+    assert!(table.contains_key("servers"));
+    assert_eq!(Value::Integer(1).into_string(), None);
+}
+
+#[test]
+fn mut_accessors() {
+    let mut v = doc();
+
+    v["deps"].as_array_mut().unwrap().push(Value::Table(Table::new()));
+    assert_eq!(v["deps"].as_array().map(|a| a.len()), Some(3));
+
+    v["servers"]["alpha"]
+        .as_table_mut()
+        .unwrap()
+        .insert("port".to_string(), Value::Integer(8080));
+    assert_eq!(v["servers"]["alpha"]["port"].as_integer(), Some(8080));
+
+    assert!(v["title"].as_array_mut().is_none());
+    assert!(v["title"].as_table_mut().is_none());
+    assert!(v["deps"].as_table_mut().is_none());
+    assert!(v["servers"].as_array_mut().is_none());
+}
+
+#[test]
+fn type_str() {
+    let v: Value = r#"
+        s = "str"
+        i = 1
+        f = 1.5
+        b = true
+        d = 1979-05-27
+        a = [1]
+        t = {}
+    "#
+    .parse()
+    .unwrap();
+
+    assert_eq!(v["s"].type_str(), "string");
+    assert_eq!(v["i"].type_str(), "integer");
+    assert_eq!(v["f"].type_str(), "float");
+    assert_eq!(v["b"].type_str(), "boolean");
+    assert_eq!(v["d"].type_str(), "datetime");
+    assert_eq!(v["a"].type_str(), "array");
+    assert_eq!(v["t"].type_str(), "table");
+}
+
+#[test]
+fn same_type() {
+    let v: Value = r#"
+        s = "str"
+        i = 1
+        f = 1.5
+        b = true
+        d = 1979-05-27
+        a = [1]
+        t = { x = 1 }
+    "#
+    .parse()
+    .unwrap();
+    let keys = ["s", "i", "f", "b", "d", "a", "t"];
+
+    for (i, a) in keys.iter().enumerate() {
+        for (j, b) in keys.iter().enumerate() {
+            assert_eq!(v[*a].same_type(&v[*b]), i == j, "{} {}", a, b);
+        }
+    }
+
+    assert!(Value::from("other").same_type(&v["s"]));
+    assert!(Value::from(vec!["x", "y"]).same_type(&v["a"]));
+    assert!(Value::Table(Table::new()).same_type(&v["t"]));
+    assert!(!Value::Integer(1).same_type(&Value::Float(1.0)));
+}
+
+#[test]
+fn loosely_eq() {
+    assert!(Value::Integer(3).loosely_eq(&Value::Float(3.0)));
+    assert!(Value::Float(-3.0).loosely_eq(&Value::Integer(-3)));
+    assert!(!Value::Integer(3).loosely_eq(&Value::Float(3.5)));
+    assert!(!Value::Integer(i64::max_value()).loosely_eq(&Value::Float(i64::max_value() as f64)));
+    assert!(Value::Integer(i64::min_value()).loosely_eq(&Value::Float(i64::min_value() as f64)));
+    assert!(!Value::Float(f64::NAN).loosely_eq(&Value::Float(f64::NAN)));
+    assert!(!Value::from("3").loosely_eq(&Value::Integer(3)));
+
+    let a: Value = "x = [1, 2]\n[t]\ny = 0".parse().unwrap();
+    let b: Value = "x = [1.0, 2.0]\n[t]\ny = -0.0".parse().unwrap();
+    assert!(a.loosely_eq(&b));
+    assert!(b.loosely_eq(&a));
+
+    let c: Value = "x = [1.0]\n[t]\ny = 0.0".parse().unwrap();
+    assert!(!a.loosely_eq(&c));
+    let d: Value = "x = [1, 2]\n[t]\ny = 0\nz = 0".parse().unwrap();
+    assert!(!a.loosely_eq(&d));
+    assert!(!d.loosely_eq(&a));
+}
+
+#[test]
+fn depth() {
+    assert_eq!(Value::Integer(1).depth(), 0);
+    assert_eq!(Value::from("x").depth(), 0);
+    assert_eq!(Value::Table(Table::new()).depth(), 1);
+    assert_eq!(Value::Array(Vec::new()).depth(), 1);
+
+    let v: Value = r#"
+        a = 1
+        b = [1, 2]
+        c = [[1], []]
+        d = { e = { f = [{}] } }
+        [g.h.i]
+        j = "deep"
+    "#
+    .parse()
+    .unwrap();
+
+    assert_eq!(v["a"].depth(), 0);
+    assert_eq!(v["b"].depth(), 1);
+    assert_eq!(v["c"].depth(), 2);
+    assert_eq!(v["d"].depth(), 4);
+    assert_eq!(v["g"].depth(), 3);
+    assert_eq!(v.depth(), 5);
+    assert_eq!(doc().depth(), 3);
+}
+
+#[test]
+fn merge() {
+    let mut base: Value = r#"
+        name = "app"
+        tags = ["a", "b"]
+
+        [server]
+        host = "localhost"
+        port = 80
+    "#
+    .parse()
+    .unwrap();
+    let overlay: Value = r#"
+        tags = ["c"]
+
+        [server]
+        port = 8080
+        tls = true
+
+        [logging]
+        level = "debug"
+    "#
+    .parse()
+    .unwrap();
+
+    base.merge(overlay);
+    assert_eq!(base["name"].as_str(), Some("app"));
+    assert_eq!(base["tags"], Value::from(vec!["c"]));
+    assert_eq!(base["server"]["host"].as_str(), Some("localhost"));
+    assert_eq!(base["server"]["port"].as_integer(), Some(8080));
+    assert_eq!(base["server"]["tls"].as_bool(), Some(true));
+    assert_eq!(base["logging"]["level"].as_str(), Some("debug"));
+
+    let mut scalar = Value::Integer(1);
+    scalar.merge(Value::from("table-free"));
+    assert_eq!(scalar.as_str(), Some("table-free"));
+
+    let mut table = doc();
+    table.merge(Value::Integer(2));
+    assert_eq!(table, Value::Integer(2));
+}
+
+#[test]
+fn merge_documents() {
+    fn docs(docs: &[(&str, &str)]) -> Vec<(String, Value)> {
+        docs.iter()
+            .map(|&(label, doc)| (label.to_string(), doc.parse().unwrap()))
+            .collect()
+    }
+
+    let merged = Value::merge_documents(docs(&[
+        ("base", "name = 'app'\n[server]\nhost = 'localhost'"),
+        ("tls", "[server.tls]\ncert = 'a.pem'"),
+        ("local", "[server]\nport = 80\n[server.tls]\nkey = 'a.key'"),
+    ]))
+    .unwrap();
+    assert_eq!(merged["name"].as_str(), Some("app"));
+    assert_eq!(merged["server"]["host"].as_str(), Some("localhost"));
+    assert_eq!(merged["server"]["port"].as_integer(), Some(80));
+    assert_eq!(merged["server"]["tls"]["cert"].as_str(), Some("a.pem"));
+    assert_eq!(merged["server"]["tls"]["key"].as_str(), Some("a.key"));
+
+    assert_eq!(
+        Value::merge_documents(Vec::new()),
+        Ok(Value::Table(Table::new()))
+    );
+
+    let err = Value::merge_documents(docs(&[
+        ("base", "[server]\nhost = 'a'"),
+        ("tls", "[server.tls]\ncert = 'a.pem'"),
+        ("local", "[server.tls]\ncert = 'b.pem'"),
+    ]))
+    .unwrap_err();
+    assert_eq!(err.path(), "server.tls.cert");
+    assert_eq!(err.sources(), ("tls", "local"));
+
+    let err = Value::merge_documents(docs(&[
+        ("base", "[server]\nhost = 'a'"),
+        ("local", "server = 'b'"),
+    ]))
+    .unwrap_err();
+    assert_eq!(err.path(), "server");
+    assert_eq!(err.sources(), ("base", "local"));
+
+    let err = Value::merge_documents(docs(&[
+        ("base", "tags = ['a']"),
+        ("other", "name = 'x'"),
+        ("local", "tags = ['a']"),
+    ]))
+    .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "`tags` is defined in both `base` and `local`"
+    );
+
+    let err = Value::merge_documents(vec![
+        ("base".to_string(), doc()),
+        ("scalar".to_string(), Value::Integer(1)),
+    ])
+    .unwrap_err();
+    assert_eq!(err.path(), "");
+    assert_eq!(
+        err.to_string(),
+        "documents `base` and `scalar` are not both tables"
+    );
+}
+
+#[test]
+fn from() {
+    assert_eq!(Value::from(5), Value::Integer(5));
+    assert_eq!(Value::from(5i64), Value::Integer(5));
+    assert_eq!(Value::from(-5i16), Value::Integer(-5));
+    assert_eq!(Value::from(65535u16), Value::Integer(65535));
+    assert_eq!(Value::from(4294967295u32), Value::Integer(4294967295));
+    assert_eq!(Value::from(1.5), Value::Float(1.5));
+    assert_eq!(Value::from(1.5f32), Value::Float(1.5));
+    assert_eq!(Value::from(true), Value::Boolean(true));
+    assert_eq!(Value::from("s"), Value::String("s".to_string()));
+    assert_eq!(Value::from("s".to_string()), Value::String("s".to_string()));
+    assert_eq!(
+        Value::from(vec![Value::from(1), Value::from("a")]),
+        Value::Array(vec![Value::Integer(1), Value::String("a".to_string())])
+    );
+
+    let mut map = BTreeMap::new();
+    map.insert("port".to_string(), Value::from(8080));
+    let mut table = Table::new();
+    table.insert("port".to_string(), Value::Integer(8080));
+    assert_eq!(Value::from(map), Value::Table(table.clone()));
+    assert_eq!(Value::from(table.clone()), Value::Table(table));
+
+    let mut port = BTreeMap::new();
+    port.insert("port", 8080);
+    let mut server = BTreeMap::new();
+    server.insert("server", Value::from(port));
+    assert_eq!(
+        toml::to_string(&Value::from(server)).unwrap(),
+        "[server]\nport = 8080\n"
+    );
+}
+
+#[test]
+fn iter_leaves() {
+    let v: Value = r#"
+        title = "example"
+        empty = []
+        nested = [[1, 2], [3]]
+
+        [servers.alpha]
+        ip = "10.0.0.1"
+        port = 8080
+
+        [servers.beta]
+
+        [[deps]]
+        name = "serde"
+
+        [[deps]]
+        name = "chrono"
+        features = ["serde"]
+    "#
+    .parse()
+    .unwrap();
+
+    let mut leaves = v
+        .iter_leaves()
+        .map(|(path, value)| (path, value.clone()))
+        .collect::<Vec<_>>();
+    // Leaves follow the order of the tables, which depends on whether the
+    // `preserve_order` feature is enabled.
+    leaves.sort_by(|a, b| a.0.cmp(&b.0));
+    assert_eq!(
+        leaves,
+        vec![
+            ("deps.0.name".to_string(), Value::from("serde")),
+            ("deps.1.features.0".to_string(), Value::from("serde")),
+            ("deps.1.name".to_string(), Value::from("chrono")),
+            ("nested.0.0".to_string(), Value::from(1)),
+            ("nested.0.1".to_string(), Value::from(2)),
+            ("nested.1.0".to_string(), Value::from(3)),
+            ("servers.alpha.ip".to_string(), Value::from("10.0.0.1")),
+            ("servers.alpha.port".to_string(), Value::from(8080)),
+            ("title".to_string(), Value::from("example")),
+        ]
+    );
+    for (path, value) in v.iter_leaves() {
+        assert_eq!(v.lookup(&path), Some(value));
+    }
+
+    let scalar = Value::from(1);
+    assert_eq!(scalar.iter_leaves().collect::<Vec<_>>(), vec![(String::new(), &scalar)]);
+    assert_eq!(Value::Table(Table::new()).iter_leaves().count(), 0);
+}
+
+#[test]
+fn flatten() {
+    let v: Value = r#"
+        title = "example"
+        empty = []
+        nested = [[1, 2], [3]]
+        "a.b" = 1
+
+        [servers.alpha]
+        ip = "10.0.0.1"
+
+        [servers.beta]
+
+        [[deps]]
+        name = "serde"
+    "#
+    .parse()
+    .unwrap();
+
+    let flat = v.flatten();
+    let paths = flat.keys().map(|k| &k[..]).collect::<Vec<_>>();
+    assert_eq!(
+        paths,
+        [
+            "a.b",
+            "deps.0.name",
+            "nested.0.0",
+            "nested.0.1",
+            "nested.1.0",
+            "servers.alpha.ip",
+            "title",
+        ]
+    );
+    assert_eq!(flat["nested.1.0"], Value::Integer(3));
+    assert_eq!(flat["deps.0.name"].as_str(), Some("serde"));
+
+    let mut rebuilt = Value::Table(Table::new());
+    for (path, value) in flat {
+        rebuilt.insert(&path, value).unwrap();
+    }
+    assert_eq!(rebuilt["servers"]["alpha"], v["servers"]["alpha"]);
+    assert_eq!(rebuilt["deps"]["0"]["name"].as_str(), Some("serde"));
+
+    let scalar = Value::Integer(1).flatten();
+    assert_eq!(scalar.len(), 1);
+    assert_eq!(scalar[""], Value::Integer(1));
+    assert!(Value::Table(Table::new()).flatten().is_empty());
+
+    // `a` is visited before `"a.b"` whether or not the keys are sorted.
+    let mut table = Table::new();
+    table.insert("a".to_string(), "b = 2".parse().unwrap());
+    table.insert("a.b".to_string(), Value::Integer(1));
+    let flat = Value::Table(table).flatten();
+    assert_eq!(flat.len(), 1);
+    assert_eq!(flat["a.b"], Value::Integer(1));
+    let unflattened = Value::unflatten(flat).unwrap();
+    assert_eq!(unflattened["a"]["b"], Value::Integer(1));
+}
+
+#[test]
+fn unflatten() {
+    let v: Value = r#"
+        title = "example"
+        nested = [[1, 2], [3]]
+        numbers = { 0 = "zero", 2 = "two" }
+
+        [servers.alpha]
+        ip = "10.0.0.1"
+        ports = [80, 443, 8000, 8001, 8002, 8003, 8004, 8005, 8006, 8007, 8008]
+
+        [[deps]]
+        name = "serde"
+
+        [[deps]]
+        name = "chrono"
+    "#
+    .parse()
+    .unwrap();
+    let unflattened = Value::unflatten(v.flatten()).unwrap();
+    assert_eq!(sort_keys(&unflattened), sort_keys(&v));
+    assert_eq!(
+        Value::unflatten(BTreeMap::new()),
+        Ok(Value::Table(Table::new()))
+    );
+
+    let mut flat = BTreeMap::new();
+    flat.insert("a.1".to_string(), Value::from(1));
+    flat.insert("b.0.c".to_string(), Value::from(2));
+    let v = Value::unflatten(flat).unwrap();
+    assert_eq!(v["a"]["1"].as_integer(), Some(1));
+    assert!(v["a"].is_table());
+    assert_eq!(v["b"][0]["c"].as_integer(), Some(2));
+    assert!(v["b"].is_array());
+
+    let mut flat = BTreeMap::new();
+    flat.insert("a.b".to_string(), Value::from(1));
+    flat.insert("a.b.c".to_string(), Value::from(2));
+    let err = Value::unflatten(flat).unwrap_err();
+    assert_eq!(err.path(), "a.b");
+    assert_eq!(
+        err.to_string(),
+        "cannot insert into `a.b`, which is of type integer"
+    );
+
+    let mut flat = BTreeMap::new();
+    flat.insert("".to_string(), Value::from(1));
+    flat.insert("a".to_string(), Value::from(2));
+    assert_eq!(Value::unflatten(flat).unwrap_err().path(), "");
+}
+
+#[test]
+fn diff() {
+    use toml::value::Change;
+
+    let old = doc();
+    assert!(old.diff(&old).is_empty());
+
+    let new: Value = r#"
+        title = "renamed"
+
+        [servers.alpha]
+        ip = "10.0.0.1"
+        port = 80
+
+        [servers.beta]
+        ip = "10.0.0.2"
+
+        [[deps]]
+        name = "serde"
+    "#
+    .parse()
+    .unwrap();
+
+    let mut beta = Table::new();
+    beta.insert("ip".to_string(), Value::from("10.0.0.2"));
+    // Changes follow the order of the tables, which depends on whether the
+    // `preserve_order` feature is enabled.
+    let mut changes = old.diff(&new);
+    changes.sort_by_key(|change| match *change {
+        Change::Added { ref path, .. }
+        | Change::Removed { ref path, .. }
+        | Change::Modified { ref path, .. } => path.clone(),
+    });
+    assert_eq!(
+        changes,
+        vec![
+            Change::Modified {
+                path: "deps".to_string(),
+                old: old["deps"].clone(),
+                new: new["deps"].clone(),
+            },
+            Change::Added {
+                path: "servers.alpha.port".to_string(),
+                value: Value::from(80),
+            },
+            Change::Added {
+                path: "servers.beta".to_string(),
+                value: Value::Table(beta),
+            },
+            Change::Modified {
+                path: "title".to_string(),
+                old: Value::from("example"),
+                new: Value::from("renamed"),
+            },
+        ]
+    );
+    assert_eq!(
+        new.diff(&old)[1],
+        Change::Removed {
+            path: "servers.alpha.port".to_string(),
+            value: Value::from(80),
+        }
+    );
+
+    assert_eq!(
+        Value::from(1).diff(&Value::from(2)),
+        vec![Change::Modified {
+            path: String::new(),
+            old: Value::from(1),
+            new: Value::from(2),
+        }]
+    );
+    assert_eq!(old["servers"].diff(&Value::from(1)).len(), 1);
+}
+
+#[test]
+fn try_into_max_depth() {
+    // Dropping the nested value recurses, so give it some room to do so.
+    let child = std::thread::Builder::new()
+        .stack_size(64 * 1024 * 1024)
+        .spawn(|| {
+            let mut value = Value::Array(Vec::new());
+            for _ in 0..10_000 {
+                value = Value::Array(vec![value]);
+            }
+            let msg = "tables and arrays are nested deeper than the maximum of 128";
+            let err = Vec::<Value>::deserialize(&value).unwrap_err();
+            assert!(err.to_string().starts_with(msg), "{}", err);
+            let err = Value::deserialize(value.clone()).unwrap_err();
+            assert!(err.to_string().starts_with(msg), "{}", err);
+            let err = value.try_into::<Vec<Value>>().unwrap_err();
+            assert!(err.to_string().starts_with(msg), "{}", err);
+
+            let input = format!("a = {}{}", "[".repeat(10_000), "]".repeat(10_000));
+            assert!(toml::from_str::<Value>(&input).is_err());
+        })
+        .unwrap();
+    child.join().unwrap();
+
+    let mut value = Value::Integer(1);
+    for _ in 0..3 {
+        let mut table = Table::new();
+        table.insert("a".to_string(), value);
+        value = Value::Table(table);
+    }
+    assert!(value.clone().try_into_with_max_depth::<Value>(2).is_ok());
+    assert!(Value::deserialize(&value).is_ok());
+    let err = value.try_into_with_max_depth::<Value>(1).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "tables and arrays are nested deeper than the maximum of 1 for key `a.a`"
+    );
+}
+
+#[test]
+fn ordering() {
+    use std::cmp::Ordering;
+
+    let values = doc();
+    let mut sorted = vec![
+        Value::from(vec![1, 2]),
+        Value::Boolean(true),
+        Value::Float(0.5),
+        values["servers"].clone(),
+        Value::Integer(3),
+        Value::from("b"),
+        Value::Integer(-1),
+        Value::from("a"),
+        Value::from(vec![1]),
+    ];
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    assert_eq!(
+        sorted,
+        vec![
+            Value::from("a"),
+            Value::from("b"),
+            Value::Integer(-1),
+            Value::Integer(3),
+            Value::Float(0.5),
+            Value::Boolean(true),
+            Value::from(vec![1]),
+            Value::from(vec![1, 2]),
+            values["servers"].clone(),
+        ]
+    );
+
+    assert!(Value::Integer(1) < Value::Float(0.0));
+    assert_ne!(Value::Integer(1), Value::Float(1.0));
+    assert_eq!(Value::Float(::std::f64::NAN).partial_cmp(&Value::Float(0.0)), None);
+
+    let early: Value = "a = 1979-05-27T07:32:00Z".parse().unwrap();
+    let late: Value = "a = 1979-05-27T00:32:00-08:00".parse().unwrap();
+    let date: Value = "a = 1979-05-28".parse().unwrap();
+    assert!(early["a"] < late["a"]);
+    assert!(late["a"] < date["a"]);
+
+    let a: Value = "x = 1\ny = 2".parse().unwrap();
+    let b: Value = "x = 1\ny = 3".parse().unwrap();
+    let c: Value = "x = 1".parse().unwrap();
+    assert!(a < b);
+    assert!(c < a);
+    assert_eq!(a.partial_cmp(&a.clone()), Some(Ordering::Equal));
+}
+
+#[test]
+fn keys() {
+    let v = doc();
+    let mut keys = v.keys().unwrap().collect::<Vec<_>>();
+    keys.sort();
+    assert_eq!(keys, ["deps", "servers", "title"]);
+    assert_eq!(v["servers"].keys().unwrap().collect::<Vec<_>>(), ["alpha"]);
+    assert_eq!(v["deps"][0].keys().unwrap().len(), 1);
+    assert!(v["deps"].keys().is_none());
+    assert!(v["title"].keys().is_none());
+    assert_eq!(Value::Table(Table::new()).keys().unwrap().next(), None);
+}
+
+#[test]
+fn keys_cloned() {
+    let mut v = doc();
+    let mut keys = v.keys_cloned().unwrap();
+    keys.sort();
+    assert_eq!(keys, ["deps", "servers", "title"]);
+    assert_eq!(v["servers"].keys_cloned().unwrap(), ["alpha"]);
+    assert!(v["deps"].keys_cloned().is_none());
+    assert!(v["title"].keys_cloned().is_none());
+    assert!(Value::Table(Table::new()).keys_cloned().unwrap().is_empty());
+
+    for key in keys {
+        v.remove(&key);
+    }
+    assert_eq!(v, Value::Table(Table::new()));
+}
+
+#[test]
+fn redact() {
+    let mut v: Value = r#"
+        password = "top"
+        tokens = ["a", "b"]
+
+        [db]
+        user = "admin"
+        db_password = "hunter2"
+        port = 5432
+
+        [[db.replicas]]
+        password = 1234
+        host = "a"
+
+        [secrets.password]
+        hint = "ask"
+    "#
+    .parse()
+    .unwrap();
+
+    v.redact(|key| key.contains("password"));
+    assert_eq!(v["password"].as_str(), Some("***"));
+    assert_eq!(v["db"]["db_password"].as_str(), Some("***"));
+    assert_eq!(v["db"]["replicas"][0]["password"].as_str(), Some("***"));
+    assert_eq!(v["db"]["replicas"][0]["host"].as_str(), Some("a"));
+    assert_eq!(v["db"]["user"].as_str(), Some("admin"));
+    assert_eq!(v["db"]["port"].as_integer(), Some(5432));
+    assert_eq!(v["secrets"]["password"]["hint"].as_str(), Some("***"));
+
+    v.redact(|path| path == "tokens.1");
+    assert_eq!(v["tokens"], Value::from(vec!["a", "***"]));
+
+    let before = v.clone();
+    v.redact(|key| key == "db" || key == "tokens");
+    assert_eq!(v, before);
+}
+
+#[test]
+fn canonicalize() {
+    let mut v: Value = r#"
+        a = 1979-05-27T00:32:00.5-07:00
+        b = 1979-05-27T07:32:00+00:00
+        c = [-0.0, -nan]
+        d = 1979-05-27T07:32:00
+        e = 3.0
+        i = 3
+
+        [t]
+        f = 07:32:00
+        g = 1979-05-27
+    "#
+    .parse()
+    .unwrap();
+    let before = v.clone();
+    v.canonicalize();
+
+    assert_eq!(v["a"], before["a"]);
+    assert_eq!(v["a"].as_datetime().unwrap().to_string(), "1979-05-27T07:32:00.500Z");
+    assert_eq!(v["b"].as_datetime().unwrap().to_string(), "1979-05-27T07:32:00Z");
+    let floats = v["c"].as_array().unwrap();
+    assert_eq!(floats[0], Value::Float(0.0));
+    assert!(floats[0].as_float().unwrap().is_sign_positive());
+    assert!(floats[1].as_float().unwrap().is_nan());
+    assert!(floats[1].as_float().unwrap().is_sign_positive());
+    assert_eq!(v["e"], Value::Float(3.0));
+    assert_eq!(v["i"], Value::Integer(3));
+    assert_eq!(v["d"], before["d"]);
+    assert_eq!(v["t"], before["t"]);
+
+    let mut other: Value = "b = 1979-05-26T23:32:00-08:00".parse().unwrap();
+    other.canonicalize();
+    assert_eq!(
+        other["b"].as_datetime().unwrap().to_string(),
+        v["b"].as_datetime().unwrap().to_string()
+    );
+}
+
+#[test]
+fn sort_arrays() {
+    let mut v: Value = r#"
+        ints = [3, -1, 2]
+        floats = [2.5, nan, -1.0, 0.5]
+        strings = ["b", "C", "a"]
+        dates = [1979-05-28, 1979-05-27]
+        nested = [[2, 1], ["b", "a"]]
+        tables = [{ n = 2, tags = ["y", "x"] }, { n = 1 }]
+
+        [[servers]]
+        name = "b"
+
+        [[servers]]
+        name = "a"
+    "#
+    .parse()
+    .unwrap();
+    v.sort_arrays();
+
+    assert_eq!(v["ints"], Value::from(vec![-1, 2, 3]));
+    let floats = v["floats"].as_array().unwrap();
+    assert_eq!(&floats[..3], &[Value::Float(-1.0), Value::Float(0.5), Value::Float(2.5)][..]);
+    assert!(floats[3].as_float().unwrap().is_nan());
+    assert_eq!(v["strings"], Value::from(vec!["C", "a", "b"]));
+    assert_eq!(v["dates"][0].to_string(), "1979-05-27");
+
+    // Only the inner arrays are sorted, not the arrays of arrays and tables.
+    assert_eq!(v["nested"][0], Value::from(vec![1, 2]));
+    assert_eq!(v["nested"][1], Value::from(vec!["a", "b"]));
+    assert_eq!(v["tables"][0]["n"].as_integer(), Some(2));
+    assert_eq!(v["tables"][0]["tags"], Value::from(vec!["x", "y"]));
+    assert_eq!(v["servers"][0]["name"].as_str(), Some("b"));
+
+    let mut mixed = Value::Array(vec![Value::Integer(2), Value::from("a"), Value::Integer(1)]);
+    let before = mixed.clone();
+    mixed.sort_arrays();
+    assert_eq!(mixed, before);
+}
+
+#[test]
+fn walk_mut() {
+    let mut v: Value = "a = 1\nb = [[2], [3]]\n[c]\nd = 'x'".parse().unwrap();
+    let mut seen = Vec::new();
+    v.walk_mut(|value| seen.push(value.type_str()));
+    assert_eq!(
+        seen,
+        ["table", "integer", "array", "array", "integer", "array", "integer", "table", "string"]
+    );
+
+    // Values put in place by the closure are walked into as well.
+    v.walk_mut(|value| match *value {
+        Value::Integer(i) if i > 1 => *value = Value::Array(vec![Value::Integer(i - 1); 2]),
+        Value::Integer(i) => *value = Value::Integer(i * 10),
+        _ => {}
+    });
+    assert_eq!(v["a"].as_integer(), Some(10));
+    assert_eq!(v["b"][0][0], Value::Array(vec![Value::Integer(10); 2]));
+    assert_eq!(v["b"][1][0][0], Value::Array(vec![Value::Integer(10); 2]));
+}
+
+#[test]
+fn hash() {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+
+    fn hash(value: &Value) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        value.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    assert_eq!(hash(&doc()), hash(&doc()));
+    assert_ne!(hash(&doc()), hash(&Value::Table(Table::new())));
+    assert_eq!(hash(&Value::Float(0.0)), hash(&Value::Float(-0.0)));
+    assert_ne!(hash(&Value::Float(1.0)), hash(&Value::Integer(1)));
+    assert_ne!(hash(&Value::from("a")), hash(&Value::from(vec!["a"])));
+
+    let a: Value = "t = 1979-05-27T00:32:00-07:00".parse().unwrap();
+    let b: Value = "t = 1979-05-27T07:32:00Z".parse().unwrap();
+    assert_eq!(a, b);
+    assert_eq!(hash(&a), hash(&b));
+
+    let mut edited = doc();
+    edited.insert("servers.alpha.ip", Value::from("10.0.0.2")).unwrap();
+    assert_ne!(hash(&doc()), hash(&edited));
+}
@@ -0,0 +1,36 @@
+#![cfg(feature = "preserve_order")]
+
+extern crate toml;
+
+#[test]
+fn round_trip_key_order() {
+    let input = "\
+zebra = 1
+apple = 2
+mango = 3
+
+[tables.z]
+b = 1
+a = 2
+
+[tables.a]
+d = 1
+c = 2
+";
+    let value: toml::Value = input.parse().unwrap();
+    let keys = value.as_table().unwrap().keys().collect::<Vec<_>>();
+    assert_eq!(keys, ["zebra", "apple", "mango", "tables"]);
+    assert_eq!(toml::to_string(&value).unwrap(), input);
+}
+
+#[test]
+fn edits_keep_key_order() {
+    let mut value: toml::Value = "c = 1\na = 2\nb = 3\n".parse().unwrap();
+    {
+        let table = value.as_table_mut().unwrap();
+        table.remove("a");
+        table.insert("d".to_string(), toml::Value::Integer(4));
+        table.insert("c".to_string(), toml::Value::Integer(5));
+    }
+    assert_eq!(toml::to_string(&value).unwrap(), "c = 5\nb = 3\nd = 4\n");
+}